@@ -0,0 +1,135 @@
+use aspmatch::{dump_match_as_binary, dump_match_as_text, IPMatch, IPRecord};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::io::{self, Write};
+
+const PAIRS: usize = 20_000;
+const NDESC: usize = 32;
+
+fn ipmatch() -> IPMatch {
+    let record = |i: usize| IPRecord {
+        x: i as f32 * 0.5,
+        y: i as f32 * 0.25,
+        xi: i as i32,
+        yi: i as i32,
+        orientation: 0.1,
+        scale: 1.5,
+        interest: 0.75,
+        polarity: 1,
+        octave: 2,
+        scale_lvl: 3,
+        desc: (0..NDESC).map(|d| d as f32 / NDESC as f32).collect(),
+    };
+    IPMatch {
+        image_1: (0..PAIRS).map(record).collect(),
+        image_2: (0..PAIRS).map(record).collect(),
+    }
+}
+
+/// Serialization as implemented before `write_le`: one `Vec` per field
+fn legacy_record_as_le_bytes(record: &IPRecord) -> Vec<u8> {
+    vec![
+        record.x.to_le_bytes().to_vec(),
+        record.y.to_le_bytes().to_vec(),
+        record.xi.to_le_bytes().to_vec(),
+        record.yi.to_le_bytes().to_vec(),
+        record.orientation.to_le_bytes().to_vec(),
+        record.scale.to_le_bytes().to_vec(),
+        record.interest.to_le_bytes().to_vec(),
+        record.polarity.to_le_bytes().to_vec(),
+        record.octave.to_le_bytes().to_vec(),
+        record.scale_lvl.to_le_bytes().to_vec(),
+        (record.desc.len() as u64).to_le_bytes().to_vec(),
+        record
+            .desc
+            .iter()
+            .flat_map(|e| e.to_le_bytes().to_vec())
+            .collect(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn legacy_match_as_le_bytes(ipmatch: &IPMatch) -> Vec<u8> {
+    let size_1_bytes = (ipmatch.image_1.len() as u64).to_le_bytes().into_iter();
+    let size_2_bytes = (ipmatch.image_2.len() as u64).to_le_bytes().into_iter();
+    let image_1_bytes = ipmatch.image_1.iter().flat_map(legacy_record_as_le_bytes);
+    let image_2_bytes = ipmatch.image_2.iter().flat_map(legacy_record_as_le_bytes);
+    size_1_bytes
+        .chain(size_2_bytes.chain(image_1_bytes.chain(image_2_bytes)))
+        .collect()
+}
+
+/// Serialization as implemented before `write_text`: one `String` per record
+fn legacy_match_as_text(ipmatch: &IPMatch) -> String {
+    let record_as_text = |r: &IPRecord| {
+        format!(
+            "{} {} {} {} {} {} {} {} {} {} {} {}",
+            r.x,
+            r.y,
+            r.xi,
+            r.yi,
+            r.orientation,
+            r.scale,
+            r.interest,
+            r.polarity,
+            r.octave,
+            r.scale_lvl,
+            r.desc.len(),
+            r.desc
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        )
+    };
+    let header = format!("{} {}", ipmatch.image_1.len(), ipmatch.image_2.len());
+    let image_1_txt = ipmatch
+        .image_1
+        .iter()
+        .map(record_as_text)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let image_2_txt = ipmatch
+        .image_2
+        .iter()
+        .map(record_as_text)
+        .collect::<Vec<String>>()
+        .join("\n");
+    [
+        header,
+        "\n".to_string(),
+        image_1_txt,
+        "\n".to_string(),
+        image_2_txt,
+    ]
+    .concat()
+}
+
+fn bench_binary(c: &mut Criterion) {
+    let ipmatch = ipmatch();
+    let mut group = c.benchmark_group("binary");
+    group.bench_function("legacy as_le_bytes", |b| {
+        b.iter(|| io::sink().write_all(&legacy_match_as_le_bytes(black_box(&ipmatch))))
+    });
+    group.bench_function("dump_match_as_binary", |b| {
+        b.iter(|| dump_match_as_binary(black_box(&ipmatch), &mut io::sink()))
+    });
+    group.finish();
+}
+
+fn bench_text(c: &mut Criterion) {
+    let ipmatch = ipmatch();
+    let mut group = c.benchmark_group("text");
+    group.sample_size(20);
+    group.bench_function("legacy as_text", |b| {
+        b.iter(|| io::sink().write_all(legacy_match_as_text(black_box(&ipmatch)).as_bytes()))
+    });
+    group.bench_function("dump_match_as_text", |b| {
+        b.iter(|| dump_match_as_text(black_box(&ipmatch), &mut io::sink()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_binary, bench_text);
+criterion_main!(benches);
@@ -1,11 +1,14 @@
 extern crate clap;
 
 use aspmatch::{
-    dump_match_as_binary, dump_match_as_binary_to_path, dump_match_as_text,
-    dump_match_as_text_to_path, parse_binary_match_file_path, parse_text_match_file_path,
-    ASPMatchError, IPMatch,
+    dump_match_as_binary, dump_match_as_binary_to_path, dump_match_as_csv,
+    dump_match_as_csv_to_path_with_options, dump_match_as_geojson,
+    dump_match_as_geojson_to_path_with_options, dump_match_as_text, dump_match_as_text_to_path,
+    parse_csv_match_file_path, parse_csv_match_from_reader, parse_match_auto_from_reader,
+    parse_match_file_path_auto, ASPMatchError, CsvColumn, CsvReadOptions, CsvWriteOptions,
+    DumpOptions, GeoJsonOptions, IPMatch, MatchFormat,
 };
-use clap::{crate_authors, crate_version, App, Arg};
+use clap::{crate_version, App, Arg};
 use std::io;
 use std::path::{Path, PathBuf};
 
@@ -14,85 +17,166 @@ enum Output {
     FilePath(PathBuf),
 }
 
-fn parse_match_file_path<P: AsRef<Path>>(
-    path: P,
-    match_file_is_binary: bool,
-) -> Result<IPMatch, ASPMatchError> {
-    if match_file_is_binary {
-        parse_binary_match_file_path(path)
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    Binary,
+    Text,
+    GeoJson,
+    Csv,
+}
+
+impl OutputFormat {
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Binary => "binary",
+            OutputFormat::Text => "text",
+            OutputFormat::GeoJson => "geojson",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+fn parse_match_file_path<P: AsRef<Path>>(path: P) -> Result<(MatchFormat, IPMatch), ASPMatchError> {
+    if path.as_ref() == Path::new("-") {
+        parse_match_auto_from_reader(io::stdin().lock())
+    } else {
+        parse_match_file_path_auto(path)
+    }
+}
+
+fn parse_csv_file_path<P: AsRef<Path>>(path: P) -> Result<IPMatch, ASPMatchError> {
+    if path.as_ref() == Path::new("-") {
+        parse_csv_match_from_reader(io::stdin().lock(), &CsvReadOptions::default())
     } else {
-        parse_text_match_file_path(path)
+        parse_csv_match_file_path(path, &CsvReadOptions::default())
     }
 }
 
+fn parse_csv_columns(columns: &str) -> Result<Vec<CsvColumn>, ASPMatchError> {
+    columns
+        .split(',')
+        .map(|name| {
+            CsvColumn::from_name(name).ok_or_else(|| {
+                let message = format!("unknown csv column {}", name);
+                io::Error::new(io::ErrorKind::InvalidInput, message).into()
+            })
+        })
+        .collect()
+}
+
 fn main() -> Result<(), ASPMatchError> {
     let matches = App::new("convert")
         .version(crate_version!())
-        .author(crate_authors!("\n"))
-        .about(
-            "Convert match file binary -> text (or text -> binary if `-rev/--reverse` is provided)",
-        )
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Convert match file binary -> text (or text -> binary, input format is detected), GeoJSON or CSV")
         .arg(
             Arg::with_name("input")
                 .value_name("INPUT")
-                .help("match file")
+                .help("match file (`-` for stdin)")
                 .required(true)
                 .takes_value(true),
         )
         .arg(
             Arg::with_name("output")
                 .value_name("OUTPUT")
-                .help("match file (if omitted print to stdout instead)")
+                .help("match file (if omitted or `-` print to stdout instead)")
                 .required(false)
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name("rev")
-                .short("rev")
-                .long("reverse")
-                .help("reverse conversion: text -> binary")
-                .required(false)
-                .takes_value(false),
+            Arg::with_name("to")
+                .long("to")
+                .value_name("FORMAT")
+                .help("output format (default: binary for text or csv input, text for binary input)")
+                .possible_values(&["binary", "text", "geojson", "csv"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .value_name("FORMAT")
+                .help("input format, csv pairs need `x1`, `y1`, `x2` and `y2` columns")
+                .possible_values(&["auto", "csv"])
+                .default_value("auto")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .value_name("COLUMNS")
+                .help("csv: comma separated output columns, e.g. x1,y1,x2,y2,interest1,ndesc1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("flip-y")
+                .long("flip-y")
+                .help("geojson: write -y, to overlay raw images in QGIS"),
         )
         .get_matches();
     let input_file = matches.value_of("input").expect("input is required");
     let output_file = matches.value_of("output");
-    let reverse = matches.is_present("rev");
 
     let output = match output_file {
-        None => Output::Stdout,
+        None | Some("-") => Output::Stdout,
         Some(output_path) => Output::FilePath(PathBuf::from(output_path)),
     };
 
-    match parse_match_file_path(PathBuf::from(input_file), !reverse) {
-        Ok(ipmatch) => {
-            match output {
-                Output::Stdout => {
-                    let stdout = io::stdout();
-                    let mut handle = stdout.lock();
-                    if reverse {
-                        // text -> binary
-                        dump_match_as_binary(&ipmatch, &mut handle)?;
-                    } else {
-                        // binary -> text
-                        dump_match_as_text(&ipmatch, &mut handle)?;
-                    }
-                }
-                Output::FilePath(path) => {
-                    if reverse {
-                        // text -> binary
-                        dump_match_as_binary_to_path(&ipmatch, &path)?;
-                        println!("{} (text) -> {:?} (binary)", input_file, path)
-                    } else {
-                        // binary -> text
-                        dump_match_as_text_to_path(&ipmatch, &path)?;
-                        println!("{} (binary) -> {:?} (text)", input_file, path)
-                    }
-                }
+    let (input_format, ipmatch) = match matches.value_of("from") {
+        Some("csv") => ("csv", parse_csv_file_path(input_file)?),
+        _ => match parse_match_file_path(PathBuf::from(input_file))? {
+            (MatchFormat::Text, ipmatch) => ("text", ipmatch),
+            (MatchFormat::Binary, ipmatch) => ("binary", ipmatch),
+        },
+    };
+    let output_format = match (matches.value_of("to"), input_format) {
+        (Some("text"), _) | (None, "binary") => OutputFormat::Text,
+        (Some("geojson"), _) => OutputFormat::GeoJson,
+        (Some("csv"), _) => OutputFormat::Csv,
+        _ => OutputFormat::Binary,
+    };
+    let mut csv = CsvWriteOptions::default();
+    if let Some(columns) = matches.value_of("columns") {
+        csv.columns = parse_csv_columns(columns)?;
+    }
+    let geojson = GeoJsonOptions {
+        flip_y: matches.is_present("flip-y"),
+        ..GeoJsonOptions::default()
+    };
+    match output {
+        Output::Stdout => {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            match output_format {
+                OutputFormat::Binary => dump_match_as_binary(&ipmatch, &mut handle)?,
+                OutputFormat::Text => dump_match_as_text(&ipmatch, &mut handle)?,
+                OutputFormat::GeoJson => dump_match_as_geojson(&ipmatch, &mut handle, &geojson)?,
+                OutputFormat::Csv => dump_match_as_csv(&ipmatch, &mut handle, &csv)?,
             }
         }
-        Err(e) => {
-            return Err(e);
+        Output::FilePath(path) => {
+            match output_format {
+                OutputFormat::Binary => dump_match_as_binary_to_path(&ipmatch, &path)?,
+                OutputFormat::Text => dump_match_as_text_to_path(&ipmatch, &path)?,
+                OutputFormat::GeoJson => dump_match_as_geojson_to_path_with_options(
+                    &ipmatch,
+                    &path,
+                    &geojson,
+                    &DumpOptions::default(),
+                )?,
+                OutputFormat::Csv => dump_match_as_csv_to_path_with_options(
+                    &ipmatch,
+                    &path,
+                    &csv,
+                    &DumpOptions::default(),
+                )?,
+            }
+            println!(
+                "{} ({}) -> {:?} ({})",
+                input_file,
+                input_format,
+                path,
+                output_format.name()
+            )
         }
     }
     Ok(())
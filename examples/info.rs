@@ -1,28 +1,51 @@
-use aspmatch::{parse_binary_match_file_path, ASPMatchError};
-use clap::{crate_authors, crate_version, App, Arg};
-use std::path::PathBuf;
+use aspmatch::{
+    parse_ip_file_auto_from_reader, parse_ip_file_path_auto, parse_match_auto_from_reader,
+    parse_match_file_path_auto, ASPMatchError,
+};
+use clap::{crate_version, App, Arg};
+use std::io;
+use std::path::{Path, PathBuf};
 
 extern crate clap;
 
 fn main() -> Result<(), ASPMatchError> {
     let matches = App::new("info")
         .version(crate_version!())
-        .author(crate_authors!("\n"))
-        .about("Print match file content")
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Print match file or interest point file content")
         .arg(
             Arg::with_name("input")
                 .value_name("INPUT")
-                .help("match file")
+                .help("match file or .vwip file, binary or text (`-` for stdin)")
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("ip")
+                .long("ip")
+                .help("INPUT is an interest point file (implied by the .vwip extension)"),
+        )
         .get_matches();
     let input_file = matches.value_of("input").expect("INPUT is required");
-    match parse_binary_match_file_path(PathBuf::from(input_file)) {
-        Ok(ipmatch) => {
-            println!("{}", ipmatch.as_text());
-            Ok(())
-        }
-        Err(e) => Err(e),
+    let is_ip_file = matches.is_present("ip")
+        || Path::new(input_file)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("vwip"));
+    if is_ip_file {
+        let (_format, ip_file) = if input_file == "-" {
+            parse_ip_file_auto_from_reader(io::stdin().lock())?
+        } else {
+            parse_ip_file_path_auto(PathBuf::from(input_file))?
+        };
+        // IP file text already ends with a line ending
+        print!("{}", ip_file.as_text());
+    } else {
+        let (_format, ipmatch) = if input_file == "-" {
+            parse_match_auto_from_reader(io::stdin().lock())?
+        } else {
+            parse_match_file_path_auto(PathBuf::from(input_file))?
+        };
+        println!("{}", ipmatch.as_text());
     }
+    Ok(())
 }
@@ -0,0 +1,98 @@
+extern crate clap;
+
+use aspmatch::{
+    dump_match_as_binary_to_path, match_records, parse_ip_file_path_auto, ASPMatchError,
+    MatchAlgorithm, MatcherOptions,
+};
+use clap::{crate_version, App, Arg};
+use std::io;
+use std::str::FromStr;
+
+fn parse_arg<T: FromStr>(name: &str, value: &str) -> Result<T, ASPMatchError> {
+    value.parse().map_err(|_| {
+        let message = format!("invalid {} {}", name, value);
+        io::Error::new(io::ErrorKind::InvalidInput, message).into()
+    })
+}
+
+fn main() -> Result<(), ASPMatchError> {
+    let matches = App::new("ipmatch")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Match interest points of two .vwip files by descriptor")
+        .arg(
+            Arg::with_name("image1")
+                .value_name("IMAGE1")
+                .help("image 1 interest point file (.vwip)")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("image2")
+                .value_name("IMAGE2")
+                .help("image 2 interest point file (.vwip)")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .value_name("OUTPUT")
+                .help("binary match file")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ratio")
+                .long("ratio")
+                .value_name("RATIO")
+                .help("Lowe's ratio test threshold, 1.0 disables it")
+                .default_value("0.8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mutual")
+                .long("mutual")
+                .help("only keep mutual nearest neighbors"),
+        )
+        .arg(
+            Arg::with_name("kdtree")
+                .long("kdtree")
+                .value_name("CHECKS")
+                .help("approximate kd-tree search examining at most CHECKS descriptors per query")
+                .takes_value(true),
+        )
+        .get_matches();
+    let image_1 = matches.value_of("image1").expect("IMAGE1 is required");
+    let image_2 = matches.value_of("image2").expect("IMAGE2 is required");
+    let output = matches.value_of("output").expect("OUTPUT is required");
+    let algorithm = match matches.value_of("kdtree") {
+        Some(checks) => MatchAlgorithm::KdTree {
+            max_checks: parse_arg("kd-tree checks", checks)?,
+        },
+        None => MatchAlgorithm::BruteForce,
+    };
+    let options = MatcherOptions {
+        ratio: parse_arg(
+            "ratio",
+            matches.value_of("ratio").expect("ratio has a default"),
+        )?,
+        mutual: matches.is_present("mutual"),
+        algorithm,
+    };
+    let (_, ip_file_1) = parse_ip_file_path_auto(image_1)?;
+    let records_1 = ip_file_1.records;
+    let (_, ip_file_2) = parse_ip_file_path_auto(image_2)?;
+    let records_2 = ip_file_2.records;
+    let ipmatch = match_records(&records_1, &records_2, &options)?;
+    dump_match_as_binary_to_path(&ipmatch, output)?;
+    println!(
+        "{} ({} records) x {} ({} records) -> {:?} ({} pairs)",
+        image_1,
+        records_1.len(),
+        image_2,
+        records_2.len(),
+        output,
+        ipmatch.image_1.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,157 @@
+extern crate clap;
+
+use aspmatch::{
+    build_tracks_from_directory, dump_match_as_binary_to_path,
+    dump_tracks_as_isis_pvl_to_path_with_options, match_file_path, parse_isis_pvl_file_path,
+    ASPMatchError, DumpOptions, IsisOptions, TrackOptions,
+};
+use clap::{crate_version, App, Arg, SubCommand};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// `(image, serial number)` from `IMAGE SERIAL_NUMBER` lines
+fn read_serial_numbers(path: &str) -> Result<Vec<(String, String)>, ASPMatchError> {
+    let mut serial_numbers = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (image, serial_number) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            let message = format!("expected IMAGE SERIAL_NUMBER, found {}", line);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })?;
+        serial_numbers.push((image.to_string(), serial_number.trim().to_string()));
+    }
+    Ok(serial_numbers)
+}
+
+fn main() -> Result<(), ASPMatchError> {
+    let serials = Arg::with_name("serials")
+        .long("serials")
+        .value_name("FILE")
+        .help("IMAGE SERIAL_NUMBER lines, e.g. from ISIS getsn")
+        .required(true)
+        .takes_value(true);
+    let matches = App::new("isis")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Convert between ASP match files and ISIS PVL control networks")
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export the tracks of a directory of match files as a control network")
+                .arg(
+                    Arg::with_name("directory")
+                        .value_name("DIRECTORY")
+                        .help("directory of <prefix>-<image1>__<image2>.match files")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .value_name("OUTPUT")
+                        .help("ISIS PVL control network")
+                        .required(true),
+                )
+                .arg(serials.clone())
+                .arg(
+                    Arg::with_name("prefix")
+                        .long("prefix")
+                        .value_name("PREFIX")
+                        .help("only use match files of this ASP output prefix")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .value_name("TARGET")
+                        .help("target body, e.g. Mars")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Write the pairwise match files of a control network")
+                .arg(
+                    Arg::with_name("input")
+                        .value_name("INPUT")
+                        .help("ISIS PVL control network")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("prefix")
+                        .value_name("PREFIX")
+                        .help("ASP output prefix of the match files")
+                        .required(true),
+                )
+                .arg(serials),
+        )
+        .get_matches();
+    match matches.subcommand() {
+        ("export", Some(matches)) => {
+            let directory = matches
+                .value_of("directory")
+                .expect("DIRECTORY is required");
+            let output = matches.value_of("output").expect("OUTPUT is required");
+            // match files name images by stem
+            let serial_numbers: HashMap<String, String> =
+                read_serial_numbers(matches.value_of("serials").expect("serials is required"))?
+                    .into_iter()
+                    .map(|(image, serial_number)| {
+                        let stem = Path::new(&image)
+                            .file_stem()
+                            .map(|s| s.to_string_lossy().into_owned());
+                        (stem.unwrap_or(image), serial_number)
+                    })
+                    .collect();
+            let track_set = build_tracks_from_directory(
+                directory,
+                matches.value_of("prefix"),
+                &TrackOptions::default(),
+            )?;
+            let options = IsisOptions {
+                target_name: matches.value_of("target").unwrap_or_default().to_string(),
+                ..IsisOptions::default()
+            };
+            dump_tracks_as_isis_pvl_to_path_with_options(
+                &track_set,
+                &serial_numbers,
+                output,
+                &options,
+                &DumpOptions::default(),
+            )?;
+            println!(
+                "{} ({} tracks, {} conflicting skipped) -> {:?}",
+                directory,
+                track_set.tracks.len(),
+                track_set.conflicting_tracks().count(),
+                output
+            );
+        }
+        ("import", Some(matches)) => {
+            let input = matches.value_of("input").expect("INPUT is required");
+            let prefix = matches.value_of("prefix").expect("PREFIX is required");
+            let images: HashMap<String, String> =
+                read_serial_numbers(matches.value_of("serials").expect("serials is required"))?
+                    .into_iter()
+                    .map(|(image, serial_number)| (serial_number, image))
+                    .collect();
+            let track_set = parse_isis_pvl_file_path(input, &IsisOptions::default())?;
+            let image = |index: usize| {
+                let serial_number = &track_set.images[index];
+                images.get(serial_number).ok_or_else(|| {
+                    let message = format!("no image for serial number {}", serial_number);
+                    io::Error::new(io::ErrorKind::InvalidData, message)
+                })
+            };
+            for ((image_1, image_2), ipmatch) in track_set.pairwise_matches() {
+                let path = match_file_path(prefix, image(image_1)?, image(image_2)?);
+                dump_match_as_binary_to_path(&ipmatch, &path)?;
+                println!("{} -> {:?} ({} pairs)", input, path, ipmatch.image_1.len());
+            }
+        }
+        _ => println!("{}", matches.usage()),
+    }
+    Ok(())
+}
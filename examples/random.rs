@@ -1,5 +1,5 @@
 use aspmatch::{dump_match_as_binary_to_path, ASPMatchError, IPMatch, IPRecord};
-use clap::{crate_authors, crate_version, App, Arg};
+use clap::{crate_version, App, Arg};
 use rand::prelude::*;
 use std::path::PathBuf;
 
@@ -38,7 +38,7 @@ fn random_ipmatch() -> IPMatch {
 fn main() -> Result<(), ASPMatchError> {
     let matches = App::new("random")
         .version(crate_version!())
-        .author(crate_authors!("\n"))
+        .author(env!("CARGO_PKG_AUTHORS"))
         .about("dump random match file to disk")
         .arg(
             Arg::with_name("output")
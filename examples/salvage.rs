@@ -0,0 +1,49 @@
+extern crate clap;
+
+use aspmatch::{salvage_binary_match_file_path, ASPMatchError, SalvageOptions};
+use clap::{crate_version, App, Arg};
+use std::path::PathBuf;
+
+fn main() -> Result<(), ASPMatchError> {
+    let matches = App::new("salvage")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Recover record pairs from a truncated or corrupted binary match file")
+        .arg(
+            Arg::with_name("input")
+                .value_name("INPUT")
+                .help("binary match file")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .value_name("OUTPUT")
+                .help("write recovered pairs as a binary match file (may be INPUT)")
+                .required(false)
+                .takes_value(true),
+        )
+        .get_matches();
+    let input_file = matches.value_of("input").expect("INPUT is required");
+    let options = SalvageOptions {
+        rewrite: matches.value_of("output").map(PathBuf::from),
+        ..SalvageOptions::default()
+    };
+    let (_, report) = salvage_binary_match_file_path(input_file, &options)?;
+    println!(
+        "expected {} + {} records, decoded {} + {}, recovered {} pairs",
+        report.expected.0,
+        report.expected.1,
+        report.decoded.0,
+        report.decoded.1,
+        report.recovered_pairs
+    );
+    match report.reason {
+        None => println!("{} is complete", input_file),
+        Some(reason) => println!("decoding stopped at byte {}: {}", report.offset, reason),
+    }
+    if let Some(output) = options.rewrite {
+        println!("{} -> {:?}", input_file, output);
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+extern crate clap;
+
+use aspmatch::{build_tracks_from_directory, ASPMatchError, TrackOptions};
+use clap::{crate_version, App, Arg};
+use std::io;
+
+fn main() -> Result<(), ASPMatchError> {
+    let matches = App::new("tracks")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Build multi-image tie-point tracks from a directory of match files")
+        .arg(
+            Arg::with_name("directory")
+                .value_name("DIRECTORY")
+                .help("directory of <prefix>-<image1>__<image2>.match files")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("prefix")
+                .long("prefix")
+                .value_name("PREFIX")
+                .help("only use match files of this ASP output prefix")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tolerance")
+                .long("tolerance")
+                .value_name("PIXELS")
+                .help("interest points closer than PIXELS are the same point")
+                .default_value("0.5")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("raw")
+                .long("raw")
+                .help("ignore -clean.match files"),
+        )
+        .get_matches();
+    let directory = matches
+        .value_of("directory")
+        .expect("DIRECTORY is required");
+    let tolerance = matches
+        .value_of("tolerance")
+        .expect("tolerance has a default");
+    let options = TrackOptions {
+        tolerance: tolerance.parse().map_err(|_| {
+            let message = format!("invalid tolerance {}", tolerance);
+            io::Error::new(io::ErrorKind::InvalidInput, message)
+        })?,
+        prefer_clean: !matches.is_present("raw"),
+        ..TrackOptions::default()
+    };
+    let track_set = build_tracks_from_directory(directory, matches.value_of("prefix"), &options)?;
+    println!(
+        "{} images, {} tracks",
+        track_set.images.len(),
+        track_set.tracks.len()
+    );
+    for (images, tracks) in track_set.image_count_histogram() {
+        println!("{} images: {} tracks", images, tracks);
+    }
+    let conflicting = track_set.conflicting_tracks().count();
+    if conflicting > 0 {
+        println!("{} tracks observe an image more than once", conflicting);
+    }
+    Ok(())
+}
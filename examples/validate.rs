@@ -0,0 +1,66 @@
+extern crate clap;
+
+use aspmatch::{
+    parse_match_auto_from_reader, parse_match_file_path_auto, ASPMatchError, Severity,
+    ValidationOptions,
+};
+use clap::{crate_version, App, Arg};
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+fn main() -> Result<(), ASPMatchError> {
+    let matches = App::new("validate")
+        .version(crate_version!())
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Check a match file is usable by ASP tools, exit with status 1 on errors")
+        .arg(
+            Arg::with_name("input")
+                .value_name("INPUT")
+                .help("match file, binary or text (`-` for stdin)")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("report integer and negative coordinates as errors"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .short("q")
+                .help("only print the summary"),
+        )
+        .get_matches();
+    let input_file = matches.value_of("input").expect("INPUT is required");
+    let (_, ipmatch) = if input_file == "-" {
+        parse_match_auto_from_reader(io::stdin().lock())?
+    } else {
+        parse_match_file_path_auto(PathBuf::from(input_file))?
+    };
+    let options = ValidationOptions {
+        strict: matches.is_present("strict"),
+        ..ValidationOptions::default()
+    };
+    let issues = ipmatch.validate(&options);
+    if !matches.is_present("quiet") {
+        for issue in &issues {
+            println!("{}", issue);
+        }
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    println!(
+        "{}: {} errors, {} warnings",
+        input_file,
+        errors,
+        issues.len() - errors
+    );
+    if errors > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
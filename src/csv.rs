@@ -0,0 +1,408 @@
+use crate::data::{write_float, IPMatch, IPRecord, MatchSide};
+use crate::error::{text_error, ASPMatchError, MatchParseError};
+use crate::options::{CsvColumn, CsvReadOptions, CsvWriteOptions, DumpOptions};
+use crate::parser::{dump_to_path_atomically, text_float};
+use nom::combinator::all_consuming;
+use nom::error::ErrorKind;
+use nom::Parser;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+type CellError<'a> = nom::Err<MatchParseError<&'a str>>;
+
+fn column_side(column: CsvColumn) -> Option<MatchSide> {
+    match column {
+        CsvColumn::Pair => None,
+        CsvColumn::X(side)
+        | CsvColumn::Y(side)
+        | CsvColumn::Xi(side)
+        | CsvColumn::Yi(side)
+        | CsvColumn::Orientation(side)
+        | CsvColumn::Scale(side)
+        | CsvColumn::Interest(side)
+        | CsvColumn::Polarity(side)
+        | CsvColumn::Octave(side)
+        | CsvColumn::ScaleLvl(side)
+        | CsvColumn::Ndesc(side) => Some(side),
+    }
+}
+
+fn write_cell<W: Write>(
+    w: &mut W,
+    index: usize,
+    (record_1, record_2): (&IPRecord, &IPRecord),
+    column: CsvColumn,
+    options: &CsvWriteOptions,
+) -> io::Result<()> {
+    let record = match column_side(column) {
+        Some(MatchSide::Image2) => record_2,
+        _ => record_1,
+    };
+    let float = |w: &mut W, value| write_float(w, value, options.float_format);
+    match column {
+        CsvColumn::Pair => write!(w, "{}", index),
+        CsvColumn::X(_) => float(w, record.x),
+        CsvColumn::Y(_) => float(w, record.y),
+        CsvColumn::Xi(_) => write!(w, "{}", record.xi),
+        CsvColumn::Yi(_) => write!(w, "{}", record.yi),
+        CsvColumn::Orientation(_) => float(w, record.orientation),
+        CsvColumn::Scale(_) => float(w, record.scale),
+        CsvColumn::Interest(_) => float(w, record.interest),
+        CsvColumn::Polarity(_) => write!(w, "{}", record.polarity),
+        CsvColumn::Octave(_) => write!(w, "{}", record.octave),
+        CsvColumn::ScaleLvl(_) => write!(w, "{}", record.scale_lvl),
+        CsvColumn::Ndesc(_) => write!(w, "{}", record.desc.len()),
+    }
+}
+
+/// Dump IPMatch pairs as CSV, one line per pair with `options.columns`
+///
+/// Fails with [`ASPMatchError::UnequalSides`] if sides have different lengths.
+///
+/// ```
+/// use aspmatch::{dump_match_as_csv, CsvColumn, CsvWriteOptions, IPMatch, IPRecord, MatchSide};
+/// let a = IPRecord { x: 1.5, y: 2.0, interest: 0.25, ..IPRecord::default() };
+/// let b = IPRecord { x: 4.0, y: 6.5, desc: vec![0.0; 3], ..IPRecord::default() };
+/// let ipmatch = IPMatch::from_pairs(vec![(a, b)]);
+/// let mut options = CsvWriteOptions::default();
+/// options.columns.push(CsvColumn::Interest(MatchSide::Image1));
+/// options.columns.push(CsvColumn::Ndesc(MatchSide::Image2));
+/// let mut csv = vec![];
+/// dump_match_as_csv(&ipmatch, &mut csv, &options).unwrap();
+/// assert_eq!(String::from_utf8(csv).unwrap(), "x1,y1,x2,y2,interest1,ndesc2\n1.5,2,4,6.5,0.25,3\n");
+/// ```
+pub fn dump_match_as_csv<W: Write>(
+    ipmatch: &IPMatch,
+    w: &mut W,
+    options: &CsvWriteOptions,
+) -> Result<(), ASPMatchError> {
+    let pairs = ipmatch.pairs()?;
+    let mut w = BufWriter::new(w);
+    if options.header {
+        for (i, column) in options.columns.iter().enumerate() {
+            if i > 0 {
+                write!(w, "{}", options.delimiter)?;
+            }
+            write!(w, "{}", column.name())?;
+        }
+        writeln!(w)?;
+    }
+    for (index, pair) in pairs.enumerate() {
+        for (i, column) in options.columns.iter().enumerate() {
+            if i > 0 {
+                write!(w, "{}", options.delimiter)?;
+            }
+            write_cell(&mut w, index, pair, *column, options)?;
+        }
+        writeln!(w)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Dump IPMatch as CSV to file at path, replacing it atomically
+pub fn dump_match_as_csv_to_path<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_match_as_csv_to_path_with_options(
+        ipmatch,
+        path,
+        &CsvWriteOptions::default(),
+        &DumpOptions::default(),
+    )
+}
+
+/// Dump IPMatch as CSV with `options` to file at path atomically, following `dump_options`
+pub fn dump_match_as_csv_to_path_with_options<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+    options: &CsvWriteOptions,
+    dump_options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    // the pairing is checked before replacing the file
+    ipmatch.pair_count()?;
+    dump_to_path_atomically(path, dump_options, |file| {
+        dump_match_as_csv(ipmatch, file, options)
+    })
+}
+
+fn cell_value<'a, O, P>(cell: &'a str, parser: P) -> Result<O, CellError<'a>>
+where
+    P: Parser<&'a str, O, MatchParseError<&'a str>>,
+{
+    all_consuming(parser)(cell).map(|(_, value)| value)
+}
+
+fn read_cell<'a>(
+    record: &mut IPRecord,
+    column: CsvColumn,
+    cell: &'a str,
+) -> Result<(), CellError<'a>> {
+    use nom::character::complete as character;
+    match column {
+        CsvColumn::Pair | CsvColumn::Ndesc(_) => {}
+        CsvColumn::X(_) => record.x = cell_value(cell, text_float)?,
+        CsvColumn::Y(_) => record.y = cell_value(cell, text_float)?,
+        CsvColumn::Xi(_) => record.xi = cell_value(cell, character::i32)?,
+        CsvColumn::Yi(_) => record.yi = cell_value(cell, character::i32)?,
+        CsvColumn::Orientation(_) => record.orientation = cell_value(cell, text_float)?,
+        CsvColumn::Scale(_) => record.scale = cell_value(cell, text_float)?,
+        CsvColumn::Interest(_) => record.interest = cell_value(cell, text_float)?,
+        CsvColumn::Polarity(_) => record.polarity = cell_value(cell, character::u8)?,
+        CsvColumn::Octave(_) => record.octave = cell_value(cell, character::u32)?,
+        CsvColumn::ScaleLvl(_) => record.scale_lvl = cell_value(cell, character::u32)?,
+    }
+    Ok(())
+}
+
+/// Parse IPMatch pairs from CSV, e.g. hand-curated `x1,y1,x2,y2` tie points
+///
+/// Columns are named by the header line, or given by `options.columns`
+/// without header, and must include `x1`, `y1`, `x2` and `y2`. Empty lines
+/// and lines starting with `#` are skipped. Fields without a column keep
+/// their default value except `xi`/`yi`, rounded from `x`/`y`, and `scale`,
+/// set to `options.scale`. Descriptors are empty.
+///
+/// ```
+/// use aspmatch::{parse_csv_match, CsvReadOptions, IPRecord};
+/// let csv = "x1,y1,x2,y2\n10.6,20.2,12,24\n";
+/// let ipmatch = parse_csv_match(csv, &CsvReadOptions::default()).unwrap();
+/// let expected = IPRecord { x: 10.6, y: 20.2, xi: 11, yi: 20, scale: 1.0, ..IPRecord::default() };
+/// assert_eq!(ipmatch.image_1, vec![expected]);
+/// assert_eq!(ipmatch.image_2[0].xi, 12);
+/// ```
+pub fn parse_csv_match(input: &str, options: &CsvReadOptions) -> Result<IPMatch, ASPMatchError> {
+    let mut lines = input
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let columns: Vec<Option<CsvColumn>> = if options.header {
+        match lines.next() {
+            Some(header) => header
+                .split(options.delimiter)
+                .map(|name| CsvColumn::from_name(name.trim().trim_matches('"')))
+                .collect(),
+            None => return Ok(IPMatch::default()),
+        }
+    } else {
+        options.columns.iter().copied().map(Some).collect()
+    };
+    let required = [CsvColumn::X, CsvColumn::Y]
+        .into_iter()
+        .flat_map(|column| [column(MatchSide::Image1), column(MatchSide::Image2)]);
+    for column in required {
+        if !columns.contains(&Some(column)) {
+            let e = MatchParseError::new(input, ErrorKind::Tag)
+                .with_expected_found(format!("column {}", column.name()), "none");
+            return Err(text_error(input, nom::Err::Error(e)));
+        }
+    }
+    let derive_xi = |side| !columns.contains(&Some(CsvColumn::Xi(side)));
+    let derive_yi = |side| !columns.contains(&Some(CsvColumn::Yi(side)));
+
+    let mut ipmatch = IPMatch::default();
+    for (index, line) in lines.enumerate() {
+        let cells: Vec<&str> = line.split(options.delimiter).map(str::trim).collect();
+        if cells.len() != columns.len() {
+            let e = MatchParseError::new(line, ErrorKind::Count).with_expected_found(
+                format!("{} columns", columns.len()),
+                format!("{} columns", cells.len()),
+            );
+            return Err(text_error(input, nom::Err::Error(e)));
+        }
+        let record = IPRecord {
+            scale: options.scale,
+            ..IPRecord::default()
+        };
+        let mut pair = (record.clone(), record);
+        for (cell, column) in cells.into_iter().zip(&columns) {
+            let (column, side) = match column.and_then(|c| column_side(c).map(|side| (c, side))) {
+                Some(column_side) => column_side,
+                None => continue,
+            };
+            let record = match side {
+                MatchSide::Image1 => &mut pair.0,
+                MatchSide::Image2 => &mut pair.1,
+            };
+            read_cell(record, column, cell).map_err(|e| {
+                let e = e.map(|e| e.with_record(side, index as u64).with_field(column.name()));
+                text_error(input, e)
+            })?;
+        }
+        for side in [MatchSide::Image1, MatchSide::Image2] {
+            let record = match side {
+                MatchSide::Image1 => &mut pair.0,
+                MatchSide::Image2 => &mut pair.1,
+            };
+            if derive_xi(side) {
+                record.xi = record.x.round() as i32;
+            }
+            if derive_yi(side) {
+                record.yi = record.y.round() as i32;
+            }
+        }
+        let (record_1, record_2) = pair;
+        ipmatch.image_1.push(record_1);
+        ipmatch.image_2.push(record_2);
+    }
+    Ok(ipmatch)
+}
+
+/// Parse IPMatch pairs from a CSV reader, see [`parse_csv_match`]
+pub fn parse_csv_match_from_reader<R: Read>(
+    reader: R,
+    options: &CsvReadOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    let mut text = String::new();
+    BufReader::new(reader).read_to_string(&mut text)?;
+    parse_csv_match(&text, options)
+}
+
+/// Parse IPMatch pairs from CSV file at path, see [`parse_csv_match`]
+pub fn parse_csv_match_file_path<P: AsRef<Path>>(
+    path: P,
+    options: &CsvReadOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    parse_csv_match_from_reader(File::open(path)?, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csv::{dump_match_as_csv, parse_csv_match, parse_csv_match_from_reader};
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::error::ASPMatchError;
+    use crate::options::{CsvColumn, CsvReadOptions, CsvWriteOptions};
+
+    fn dummy_ipmatch() -> IPMatch {
+        let record = |x: f32| IPRecord {
+            x,
+            y: x * 2.0,
+            xi: x as i32,
+            yi: (x * 2.0) as i32,
+            orientation: 0.1,
+            scale: 3.0,
+            interest: 0.7,
+            polarity: 1,
+            octave: 2,
+            scale_lvl: 4,
+            desc: vec![],
+        };
+        (0..3)
+            .map(|i| (record(i as f32 + 0.25), record(i as f32 + 10.5)))
+            .collect()
+    }
+
+    fn all_columns() -> Vec<CsvColumn> {
+        let names = [
+            "pair",
+            "x1",
+            "y1",
+            "xi1",
+            "yi1",
+            "orientation1",
+            "scale1",
+            "interest1",
+            "polarity1",
+            "octave1",
+            "scale_lvl1",
+            "ndesc1",
+            "x2",
+            "y2",
+            "xi2",
+            "yi2",
+            "orientation2",
+            "scale2",
+            "interest2",
+            "polarity2",
+            "octave2",
+            "scale_lvl2",
+            "ndesc2",
+        ];
+        names
+            .iter()
+            .map(|name| CsvColumn::from_name(name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let ipmatch = dummy_ipmatch();
+        let write_options = CsvWriteOptions {
+            columns: all_columns(),
+            delimiter: ';',
+            ..CsvWriteOptions::default()
+        };
+        let mut csv = vec![];
+        dump_match_as_csv(&ipmatch, &mut csv, &write_options).unwrap();
+        let read_options = CsvReadOptions {
+            delimiter: ';',
+            ..CsvReadOptions::default()
+        };
+        let parsed = parse_csv_match_from_reader(csv.as_slice(), &read_options).unwrap();
+        assert_eq!(parsed, ipmatch);
+    }
+
+    #[test]
+    fn test_csv_defaults() {
+        let csv = "# manual points\r\n\"y2\", \"x2\",comment,Y1,X1\r\n\r\n4.5,3.5,a,-2.5,1.4\r\n";
+        let options = CsvReadOptions {
+            scale: 2.0,
+            ..CsvReadOptions::default()
+        };
+        let ipmatch = parse_csv_match(csv, &options).unwrap();
+        let record = |x, y, xi, yi| IPRecord {
+            x,
+            y,
+            xi,
+            yi,
+            scale: 2.0,
+            ..IPRecord::default()
+        };
+        assert_eq!(ipmatch.image_1, vec![record(1.4, -2.5, 1, -3)]);
+        assert_eq!(ipmatch.image_2, vec![record(3.5, 4.5, 4, 5)]);
+
+        let no_header = CsvReadOptions {
+            header: false,
+            ..CsvReadOptions::default()
+        };
+        let ipmatch = parse_csv_match("1,2,3,4\n5,6,7,8", &no_header).unwrap();
+        assert_eq!(ipmatch.pair_count().unwrap(), 2);
+        assert_eq!(ipmatch.image_2[1].y, 8.0);
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        let options = CsvReadOptions::default();
+        match parse_csv_match("x1,y1,x2\n1,2,3\n", &options).unwrap_err() {
+            ASPMatchError::TextParser { line, details, .. } => {
+                assert_eq!(line, 1);
+                assert_eq!(details.expected.as_deref(), Some("column y2"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        match parse_csv_match("x1,y1,x2,y2\n1,2,3,4\n1,2,3\n", &options).unwrap_err() {
+            ASPMatchError::TextParser { line, details, .. } => {
+                assert_eq!(line, 3);
+                assert_eq!(details.found.as_deref(), Some("3 columns"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        let e = parse_csv_match("x1,y1,xi2,x2,y2\n1,2,3,4,5\n1,2,3.5,4,5\n", &options).unwrap_err();
+        match e {
+            ASPMatchError::TextParser {
+                line,
+                column,
+                details,
+            } => {
+                assert_eq!((line, column), (3, 6));
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("xi2"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
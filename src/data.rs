@@ -1,4 +1,18 @@
+use crate::error::ASPMatchError;
+use crate::options::{ByteOrder, FloatFormat, TextWriteOptions};
+use std::cmp::Ordering;
+use std::io;
+use std::io::Write;
+use std::iter::Zip;
+use std::slice;
+
+/// Interest point record
+///
+/// With the `serde` feature, fields are serialized under their Rust names
+/// (`x`, `y`, `xi`, `yi`, `orientation`, `scale`, `interest`, `polarity`,
+/// `octave`, `scale_lvl`, `desc`), renaming a field is a breaking change.
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IPRecord {
     pub x: f32,
     pub y: f32,
@@ -28,53 +42,158 @@ impl IPRecord {
     /// assert_eq!(record.as_le_bytes(), expected);
     /// ```
     pub fn as_le_bytes(&self) -> Vec<u8> {
-        vec![
-            self.x.to_le_bytes().to_vec(),
-            self.y.to_le_bytes().to_vec(),
-            self.xi.to_le_bytes().to_vec(),
-            self.yi.to_le_bytes().to_vec(),
-            self.orientation.to_le_bytes().to_vec(),
-            self.scale.to_le_bytes().to_vec(),
-            self.interest.to_le_bytes().to_vec(),
-            self.polarity.to_le_bytes().to_vec(),
-            self.octave.to_le_bytes().to_vec(),
-            self.scale_lvl.to_le_bytes().to_vec(),
-            (self.desc.len() as u64).to_le_bytes().to_vec(),
-            self.desc
-                .iter()
-                .map(|e| e.to_le_bytes().to_vec())
-                .flatten()
-                .collect(),
-        ]
-        .into_iter()
-        .flatten()
-        .collect()
+        self.as_bytes(ByteOrder::LittleEndian)
+    }
+
+    /// Return IPRecord as bytes in given byte order
+    ///
+    /// ```
+    /// use aspmatch::{ByteOrder, IPRecord};
+    /// let record = IPRecord { x: 1.0, ..IPRecord::default() };
+    /// let bytes = record.as_bytes(ByteOrder::BigEndian);
+    /// assert_eq!(bytes[..4], 1.0f32.to_be_bytes());
+    /// ```
+    pub fn as_bytes(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let size = MINIMUM_RECORD_SIZE_IN_BYTES as usize + 4 * self.desc.len();
+        let mut bytes = Vec::with_capacity(size);
+        self.write_bytes(&mut bytes, byte_order)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Write IPRecord as little endian bytes
+    pub fn write_le<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_bytes(w, ByteOrder::LittleEndian)
+    }
+
+    /// Write IPRecord as bytes in given byte order, without intermediate allocation
+    ///
+    /// ```
+    /// use aspmatch::{ByteOrder, IPRecord};
+    /// let record = IPRecord { desc: vec![1.0, 2.0], ..IPRecord::default() };
+    /// let mut bytes = vec![];
+    /// record.write_bytes(&mut bytes, ByteOrder::LittleEndian).unwrap();
+    /// assert_eq!(bytes, record.as_le_bytes());
+    /// ```
+    pub fn write_bytes<W: Write>(&self, w: &mut W, byte_order: ByteOrder) -> io::Result<()> {
+        macro_rules! write_bytes {
+            ($value:expr) => {
+                match byte_order {
+                    ByteOrder::LittleEndian => w.write_all(&$value.to_le_bytes()),
+                    ByteOrder::BigEndian => w.write_all(&$value.to_be_bytes()),
+                }
+            };
+        }
+        write_bytes!(self.x)?;
+        write_bytes!(self.y)?;
+        write_bytes!(self.xi)?;
+        write_bytes!(self.yi)?;
+        write_bytes!(self.orientation)?;
+        write_bytes!(self.scale)?;
+        write_bytes!(self.interest)?;
+        write_bytes!(self.polarity)?;
+        write_bytes!(self.octave)?;
+        write_bytes!(self.scale_lvl)?;
+        write_bytes!(self.desc.len() as u64)?;
+        for value in &self.desc {
+            write_bytes!(value)?;
+        }
+        Ok(())
     }
 
     pub fn as_text(&self) -> String {
-        format!(
-            "{} {} {} {} {} {} {} {} {} {} {} {}",
-            self.x,
-            self.y,
-            self.xi,
-            self.yi,
-            self.orientation,
-            self.scale,
-            self.interest,
+        let mut text = vec![];
+        self.write_text(&mut text)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(text).expect("text records are ASCII")
+    }
+
+    /// Write IPRecord as text, without intermediate allocation
+    pub fn write_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_text_with_options(w, &TextWriteOptions::default())
+    }
+
+    /// Write IPRecord as text, formatting floats according to `options`
+    ///
+    /// ```
+    /// use aspmatch::{FloatFormat, IPRecord, TextWriteOptions};
+    /// let record = IPRecord { x: 0.1, y: -2.0, ..IPRecord::default() };
+    /// let options = TextWriteOptions { float_format: FloatFormat::Hex };
+    /// let mut text = vec![];
+    /// record.write_text_with_options(&mut text, &options).unwrap();
+    /// assert!(text.starts_with(b"0x1.99999ap-4 -0x1p+1 0 0 "));
+    /// ```
+    pub fn write_text_with_options<W: Write>(
+        &self,
+        w: &mut W,
+        options: &TextWriteOptions,
+    ) -> io::Result<()> {
+        let format = options.float_format;
+        write_float(w, self.x, format)?;
+        w.write_all(b" ")?;
+        write_float(w, self.y, format)?;
+        write!(w, " {} {} ", self.xi, self.yi)?;
+        write_float(w, self.orientation, format)?;
+        w.write_all(b" ")?;
+        write_float(w, self.scale, format)?;
+        w.write_all(b" ")?;
+        write_float(w, self.interest, format)?;
+        write!(
+            w,
+            " {} {} {} {} ",
             self.polarity,
             self.octave,
             self.scale_lvl,
             self.desc.len(),
-            self.desc
-                .iter()
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>()
-                .join(" ")
-        )
+        )?;
+        for (i, value) in self.desc.iter().enumerate() {
+            if i > 0 {
+                w.write_all(b" ")?;
+            }
+            write_float(w, *value, format)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a single float as text
+///
+/// NaN is written as `NaN` whatever its sign and payload, infinities as `inf`
+/// and `-inf`.
+pub(crate) fn write_float<W: Write>(w: &mut W, value: f32, format: FloatFormat) -> io::Result<()> {
+    if !value.is_finite() {
+        return write!(w, "{}", value);
+    }
+    match format {
+        FloatFormat::Shortest => write!(w, "{}", value),
+        FloatFormat::Fixed(precision) => write!(w, "{:.*}", precision, value),
+        FloatFormat::Hex => {
+            let bits = value.to_bits();
+            let sign = if value.is_sign_negative() { "-" } else { "" };
+            let biased_exponent = ((bits >> 23) & 0xff) as i32;
+            let fraction = bits & 0x7f_ffff;
+            let (leading, exponent) = match (biased_exponent, fraction) {
+                (0, 0) => (0, 0),
+                (0, _) => (0, -126), // subnormal
+                _ => (1, biased_exponent - 127),
+            };
+            // 23 fraction bits shifted left once make 6 hex digits
+            let digits = format!("{:06x}", fraction << 1);
+            let digits = digits.trim_end_matches('0');
+            if digits.is_empty() {
+                write!(w, "{}0x{}p{:+}", sign, leading, exponent)
+            } else {
+                write!(w, "{}0x{}.{}p{:+}", sign, leading, digits, exponent)
+            }
+        }
     }
 }
 
+/// Match between two images, record `i` of `image_1` matches record `i` of `image_2`
+///
+/// With the `serde` feature, serialized as `{"image_1": [..], "image_2": [..]}`.
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IPMatch {
     pub image_1: Vec<IPRecord>,
     pub image_2: Vec<IPRecord>,
@@ -90,36 +209,465 @@ impl IPMatch {
     /// assert_eq!(_match.as_le_bytes(), expected);
     /// ```
     pub fn as_le_bytes(&self) -> Vec<u8> {
-        let size_1_bytes = self.image_1.len().to_le_bytes().into_iter();
-        let size_2_bytes = self.image_2.len().to_le_bytes().into_iter();
-        let image_1_bytes = self.image_1.iter().map(|i| i.as_le_bytes()).flatten();
-        let image_2_bytes = self.image_2.iter().map(|i| i.as_le_bytes()).flatten();
-        size_1_bytes
-            .chain(size_2_bytes.chain(image_1_bytes.chain(image_2_bytes)))
-            .collect()
+        self.as_bytes(ByteOrder::LittleEndian)
+    }
+
+    /// Return IPMatch as bytes in given byte order
+    ///
+    /// Counts are always written as u64, whatever the target pointer width.
+    pub fn as_bytes(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_bytes(&mut bytes, byte_order)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Write IPMatch as little endian bytes
+    pub fn write_le<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_bytes(w, ByteOrder::LittleEndian)
+    }
+
+    /// Write IPMatch as bytes in given byte order, record by record
+    ///
+    /// Prefer a buffered writer, records are written field by field.
+    pub fn write_bytes<W: Write>(&self, w: &mut W, byte_order: ByteOrder) -> io::Result<()> {
+        let (size_1, size_2) = (self.image_1.len() as u64, self.image_2.len() as u64);
+        match byte_order {
+            ByteOrder::LittleEndian => {
+                w.write_all(&size_1.to_le_bytes())?;
+                w.write_all(&size_2.to_le_bytes())?;
+            }
+            ByteOrder::BigEndian => {
+                w.write_all(&size_1.to_be_bytes())?;
+                w.write_all(&size_2.to_be_bytes())?;
+            }
+        }
+        for record in self.image_1.iter().chain(self.image_2.iter()) {
+            record.write_bytes(w, byte_order)?;
+        }
+        Ok(())
     }
 
     pub fn as_text(&self) -> String {
-        let header = format!("{} {}", self.image_1.len(), self.image_2.len());
-        let image_1_txt = self
-            .image_1
-            .iter()
-            .map(|r| r.as_text())
-            .collect::<Vec<String>>()
-            .join("\n");
-        let image_2_txt = self
-            .image_2
-            .iter()
-            .map(|r| r.as_text())
-            .collect::<Vec<String>>()
-            .join("\n");
-        vec![
-            header,
-            "\n".to_string(),
-            image_1_txt,
-            "\n".to_string(),
-            image_2_txt,
-        ]
-        .concat()
+        let mut text = vec![];
+        self.write_text(&mut text)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(text).expect("text records are ASCII")
+    }
+
+    /// Write IPMatch as text, record by record
+    ///
+    /// Prefer a buffered writer, records are written field by field.
+    ///
+    /// ```
+    /// use aspmatch::{IPMatch, IPRecord};
+    /// let ipmatch = IPMatch { image_1: vec![IPRecord::default()], image_2: vec![] };
+    /// let mut text = vec![];
+    /// ipmatch.write_text(&mut text).unwrap();
+    /// assert_eq!(text, ipmatch.as_text().as_bytes());
+    /// ```
+    pub fn write_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_text_with_options(w, &TextWriteOptions::default())
+    }
+
+    /// Write IPMatch as text, formatting floats according to `options`
+    pub fn write_text_with_options<W: Write>(
+        &self,
+        w: &mut W,
+        options: &TextWriteOptions,
+    ) -> io::Result<()> {
+        writeln!(w, "{} {}", self.image_1.len(), self.image_2.len())?;
+        for (i, record) in self.image_1.iter().enumerate() {
+            if i > 0 {
+                w.write_all(b"\n")?;
+            }
+            record.write_text_with_options(w, options)?;
+        }
+        w.write_all(b"\n")?;
+        for (i, record) in self.image_2.iter().enumerate() {
+            if i > 0 {
+                w.write_all(b"\n")?;
+            }
+            record.write_text_with_options(w, options)?;
+        }
+        Ok(())
+    }
+}
+
+/// Interest points of a single image, as written by ASP `ipfind` in `.vwip` files
+///
+/// Binary layout: a u64 record count followed by the records, laid out as in
+/// match files. Text layout: the count on the first line, then one record per
+/// line.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IPFile {
+    pub records: Vec<IPRecord>,
+}
+
+impl IPFile {
+    /// Return IPFile as little endian bytes
+    ///
+    /// ```
+    /// use aspmatch::IPFile;
+    /// assert_eq!(IPFile::default().as_le_bytes(), vec![0; 8]); // u64 count, no record
+    /// ```
+    pub fn as_le_bytes(&self) -> Vec<u8> {
+        self.as_bytes(ByteOrder::LittleEndian)
+    }
+
+    /// Return IPFile as bytes in given byte order
+    pub fn as_bytes(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_bytes(&mut bytes, byte_order)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Write IPFile as little endian bytes
+    pub fn write_le<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_bytes(w, ByteOrder::LittleEndian)
+    }
+
+    /// Write IPFile as bytes in given byte order, record by record
+    ///
+    /// Prefer a buffered writer, records are written field by field.
+    pub fn write_bytes<W: Write>(&self, w: &mut W, byte_order: ByteOrder) -> io::Result<()> {
+        let size = self.records.len() as u64;
+        match byte_order {
+            ByteOrder::LittleEndian => w.write_all(&size.to_le_bytes())?,
+            ByteOrder::BigEndian => w.write_all(&size.to_be_bytes())?,
+        }
+        for record in &self.records {
+            record.write_bytes(w, byte_order)?;
+        }
+        Ok(())
+    }
+
+    pub fn as_text(&self) -> String {
+        let mut text = vec![];
+        self.write_text(&mut text)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(text).expect("text records are ASCII")
+    }
+
+    /// Write IPFile as text, record by record
+    ///
+    /// ```
+    /// use aspmatch::{IPFile, IPRecord};
+    /// let ip_file = IPFile { records: vec![IPRecord::default()] };
+    /// assert_eq!(ip_file.as_text(), "1\n0 0 0 0 0 0 0 0 0 0 0 \n");
+    /// ```
+    pub fn write_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_text_with_options(w, &TextWriteOptions::default())
+    }
+
+    /// Write IPFile as text, formatting floats according to `options`
+    pub fn write_text_with_options<W: Write>(
+        &self,
+        w: &mut W,
+        options: &TextWriteOptions,
+    ) -> io::Result<()> {
+        writeln!(w, "{}", self.records.len())?;
+        for record in &self.records {
+            record.write_text_with_options(w, options)?;
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Pair-oriented API, record `i` of image 1 matches record `i` of image 2
+///
+/// Methods relying on the pairing fail with [`ASPMatchError::UnequalSides`]
+/// instead of silently pairing misaligned records.
+impl IPMatch {
+    /// Build a match from `(image 1, image 2)` record pairs
+    ///
+    /// ```
+    /// use aspmatch::{IPMatch, IPRecord};
+    /// let a = IPRecord { x: 1.0, ..IPRecord::default() };
+    /// let b = IPRecord { x: 2.0, ..IPRecord::default() };
+    /// let ipmatch = IPMatch::from_pairs(vec![(a.clone(), b.clone())]);
+    /// assert_eq!(ipmatch, IPMatch { image_1: vec![a], image_2: vec![b] });
+    /// ```
+    pub fn from_pairs<I: IntoIterator<Item = (IPRecord, IPRecord)>>(pairs: I) -> Self {
+        let (image_1, image_2) = pairs.into_iter().unzip();
+        IPMatch { image_1, image_2 }
+    }
+
+    /// Build a match from both sides, checking they have the same length
+    pub fn from_sides(
+        image_1: Vec<IPRecord>,
+        image_2: Vec<IPRecord>,
+    ) -> Result<Self, ASPMatchError> {
+        let ipmatch = IPMatch { image_1, image_2 };
+        ipmatch.pair_count()?;
+        Ok(ipmatch)
+    }
+
+    /// Number of pairs, fails if sides have different lengths
+    pub fn pair_count(&self) -> Result<usize, ASPMatchError> {
+        if self.image_1.len() == self.image_2.len() {
+            Ok(self.image_1.len())
+        } else {
+            Err(ASPMatchError::UnequalSides {
+                size_1: self.image_1.len(),
+                size_2: self.image_2.len(),
+            })
+        }
+    }
+
+    /// Iterate over `(image 1, image 2)` record pairs
+    ///
+    /// ```
+    /// use aspmatch::{ASPMatchError, IPMatch, IPRecord};
+    /// let mut ipmatch = IPMatch::from_pairs(vec![(IPRecord::default(), IPRecord::default())]);
+    /// assert_eq!(ipmatch.pairs().unwrap().count(), 1);
+    /// ipmatch.image_2.clear();
+    /// assert!(matches!(ipmatch.pairs(), Err(ASPMatchError::UnequalSides { size_1: 1, size_2: 0 })));
+    /// ```
+    pub fn pairs(
+        &self,
+    ) -> Result<Zip<slice::Iter<'_, IPRecord>, slice::Iter<'_, IPRecord>>, ASPMatchError> {
+        self.pair_count()?;
+        Ok(self.image_1.iter().zip(self.image_2.iter()))
+    }
+
+    /// Iterate over mutable `(image 1, image 2)` record pairs
+    pub fn pairs_mut(
+        &mut self,
+    ) -> Result<Zip<slice::IterMut<'_, IPRecord>, slice::IterMut<'_, IPRecord>>, ASPMatchError>
+    {
+        self.pair_count()?;
+        Ok(self.image_1.iter_mut().zip(self.image_2.iter_mut()))
+    }
+
+    /// Keep only the pairs for which `f` returns `true`, on both sides
+    ///
+    /// ```
+    /// use aspmatch::{IPMatch, IPRecord};
+    /// let record = |x| IPRecord { x, ..IPRecord::default() };
+    /// let mut ipmatch = IPMatch::from_pairs((0..4).map(|i| (record(i as f32), record(-i as f32))));
+    /// ipmatch.retain_pairs(|a, _b| a.x >= 2.0).unwrap();
+    /// assert_eq!(ipmatch.image_2, vec![record(-2.0), record(-3.0)]);
+    /// ```
+    pub fn retain_pairs<F: FnMut(&IPRecord, &IPRecord) -> bool>(
+        &mut self,
+        mut f: F,
+    ) -> Result<(), ASPMatchError> {
+        self.pair_count()?;
+        let keep: Vec<bool> = self.pairs()?.map(|(a, b)| f(a, b)).collect();
+        let mut keep_1 = keep.iter();
+        self.image_1
+            .retain(|_| *keep_1.next().expect("one flag per pair"));
+        let mut keep_2 = keep.iter();
+        self.image_2
+            .retain(|_| *keep_2.next().expect("one flag per pair"));
+        Ok(())
+    }
+
+    /// Append a pair, fails without modifying the match if sides have different lengths
+    pub fn push_pair(
+        &mut self,
+        record_1: IPRecord,
+        record_2: IPRecord,
+    ) -> Result<(), ASPMatchError> {
+        self.pair_count()?;
+        self.image_1.push(record_1);
+        self.image_2.push(record_2);
+        Ok(())
+    }
+
+    /// Remove and return pair `index`, shifting the following pairs
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds, like `Vec::remove`.
+    pub fn remove_pair(&mut self, index: usize) -> Result<(IPRecord, IPRecord), ASPMatchError> {
+        self.pair_count()?;
+        Ok((self.image_1.remove(index), self.image_2.remove(index)))
+    }
+
+    /// Swap image 1 and image 2 records, e.g. to match the images the other way around
+    pub fn swap_images(&mut self) {
+        std::mem::swap(&mut self.image_1, &mut self.image_2);
+    }
+
+    /// Sort pairs with comparator `compare`, the sort is stable
+    ///
+    /// ```
+    /// use aspmatch::{IPMatch, IPRecord};
+    /// let record = |interest| IPRecord { interest, ..IPRecord::default() };
+    /// let mut ipmatch = IPMatch::from_pairs(vec![(record(0.5), record(0.1)), (record(0.9), record(0.2))]);
+    /// // strongest image 1 interest points first
+    /// ipmatch.sort_pairs_by(|(a, _), (b, _)| b.interest.total_cmp(&a.interest)).unwrap();
+    /// assert_eq!(ipmatch.image_2, vec![record(0.2), record(0.1)]);
+    /// ```
+    pub fn sort_pairs_by<F>(&mut self, mut compare: F) -> Result<(), ASPMatchError>
+    where
+        F: FnMut((&IPRecord, &IPRecord), (&IPRecord, &IPRecord)) -> Ordering,
+    {
+        self.pair_count()?;
+        let image_1 = std::mem::take(&mut self.image_1);
+        let image_2 = std::mem::take(&mut self.image_2);
+        let mut pairs: Vec<(IPRecord, IPRecord)> = image_1.into_iter().zip(image_2).collect();
+        pairs.sort_by(|(a_1, a_2), (b_1, b_2)| compare((a_1, a_2), (b_1, b_2)));
+        (self.image_1, self.image_2) = pairs.into_iter().unzip();
+        Ok(())
+    }
+}
+
+impl FromIterator<(IPRecord, IPRecord)> for IPMatch {
+    fn from_iter<I: IntoIterator<Item = (IPRecord, IPRecord)>>(pairs: I) -> Self {
+        IPMatch::from_pairs(pairs)
+    }
+}
+
+/// Image side of a match: records of the first or the second image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchSide {
+    Image1,
+    Image2,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord};
+    use crate::error::ASPMatchError;
+
+    fn record(x: f32) -> IPRecord {
+        IPRecord {
+            x,
+            ..IPRecord::default()
+        }
+    }
+
+    fn dummy_ipmatch() -> IPMatch {
+        (0..5)
+            .map(|i| (record(i as f32), record(10.0 + i as f32)))
+            .collect()
+    }
+
+    fn assert_aligned(ipmatch: &IPMatch) {
+        for (a, b) in ipmatch.pairs().unwrap() {
+            assert_eq!(a.x + 10.0, b.x);
+        }
+    }
+
+    #[test]
+    fn test_pairs_keep_alignment() {
+        let mut ipmatch = dummy_ipmatch();
+        ipmatch.retain_pairs(|a, _| a.x as i32 % 2 == 0).unwrap();
+        assert_eq!(ipmatch.pair_count().unwrap(), 3);
+        assert_aligned(&ipmatch);
+        let (a, b) = ipmatch.remove_pair(1).unwrap();
+        assert_eq!((a.x, b.x), (2.0, 12.0));
+        assert_aligned(&ipmatch);
+        ipmatch.push_pair(record(7.0), record(17.0)).unwrap();
+        ipmatch
+            .sort_pairs_by(|(_, a), (_, b)| b.x.total_cmp(&a.x))
+            .unwrap();
+        let xs: Vec<f32> = ipmatch.image_1.iter().map(|record| record.x).collect();
+        assert_eq!(xs, vec![7.0, 4.0, 0.0]);
+        assert_aligned(&ipmatch);
+        for (a, b) in ipmatch.pairs_mut().unwrap() {
+            a.y = 1.0;
+            b.y = 2.0;
+        }
+        assert!(ipmatch.image_2.iter().all(|record| record.y == 2.0));
+    }
+
+    #[test]
+    fn test_pairs_unequal_sides() {
+        let mut ipmatch = dummy_ipmatch();
+        ipmatch.image_1.pop();
+        let unequal = |result: Result<_, ASPMatchError>| {
+            matches!(
+                result,
+                Err(ASPMatchError::UnequalSides {
+                    size_1: 4,
+                    size_2: 5
+                })
+            )
+        };
+        assert!(unequal(ipmatch.pairs().map(|_| ())));
+        assert!(unequal(ipmatch.pairs_mut().map(|_| ())));
+        assert!(unequal(ipmatch.retain_pairs(|_, _| false)));
+        assert!(unequal(ipmatch.push_pair(record(0.0), record(0.0))));
+        assert!(unequal(ipmatch.remove_pair(0).map(|_| ())));
+        assert!(unequal(
+            ipmatch.sort_pairs_by(|_, _| std::cmp::Ordering::Equal)
+        ));
+        // nothing was modified
+        assert_eq!((ipmatch.image_1.len(), ipmatch.image_2.len()), (4, 5));
+        let IPMatch { image_1, image_2 } = ipmatch;
+        assert!(unequal(IPMatch::from_sides(image_1, image_2).map(|_| ())));
+    }
+
+    #[cfg(feature = "serde")]
+    fn serde_ipmatch() -> IPMatch {
+        let record = |x: f32| IPRecord {
+            x,
+            y: -x,
+            xi: x as i32,
+            yi: -x as i32,
+            orientation: 0.25,
+            scale: 1.5,
+            interest: f32::MIN_POSITIVE,
+            polarity: 1,
+            octave: 2,
+            scale_lvl: 3,
+            desc: vec![0.1, f32::MAX, -0.0],
+        };
+        (0..3)
+            .map(|i| (record(i as f32), record(0.5 + i as f32)))
+            .collect()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json() {
+        let ipmatch = serde_ipmatch();
+        let json = serde_json::to_string(&ipmatch).unwrap();
+        let parsed: IPMatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, ipmatch);
+        // field names are part of the format
+        let record = serde_json::to_value(IPRecord::default()).unwrap();
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "x": 0.0, "y": 0.0, "xi": 0, "yi": 0,
+                "orientation": 0.0, "scale": 0.0, "interest": 0.0,
+                "polarity": 0, "octave": 0, "scale_lvl": 0, "desc": []
+            })
+        );
+        let value = serde_json::to_value(IPMatch::default()).unwrap();
+        assert_eq!(value, serde_json::json!({"image_1": [], "image_2": []}));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_binary() {
+        let ipmatch = serde_ipmatch();
+        let bytes = bincode::serialize(&ipmatch).unwrap();
+        assert_eq!(bincode::deserialize::<IPMatch>(&bytes).unwrap(), ipmatch);
+        // NaN does not survive JSON but survives binary formats
+        let mut nan = ipmatch.clone();
+        nan.image_1[0].desc[0] = f32::NAN;
+        let bytes = rmp_serde::to_vec_named(&nan).unwrap();
+        let parsed: IPMatch = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(parsed.image_1[0].desc[0].is_nan());
+        let bytes = rmp_serde::to_vec(&ipmatch).unwrap();
+        assert_eq!(rmp_serde::from_slice::<IPMatch>(&bytes).unwrap(), ipmatch);
+    }
+
+    #[test]
+    fn test_swap_images() {
+        let mut ipmatch = dummy_ipmatch();
+        ipmatch.swap_images();
+        assert_eq!(ipmatch.image_1[0].x, 10.0);
+        assert_eq!(ipmatch.image_2[0].x, 0.0);
     }
 }
@@ -0,0 +1,251 @@
+use crate::data::MatchSide;
+use nom::error::{ContextError, ErrorKind, ParseError};
+use nom::{IResult, Offset};
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+/// Custom Error Enum for lib consumption.
+#[derive(Error, Debug)]
+pub enum ASPMatchError {
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("binary parser error at byte offset {offset}: {details}")]
+    BinaryParser {
+        offset: u64,
+        details: ParseErrorDetails,
+    },
+    #[error("text parser error at line {line}, column {column}: {details}")]
+    TextParser {
+        line: u64,
+        column: u64,
+        details: ParseErrorDetails,
+    },
+    #[error("unknown match file format")]
+    UnknownFormat,
+    #[error("unequal sides: {size_1} image 1 records, {size_2} image 2 records")]
+    UnequalSides { size_1: usize, size_2: usize },
+    #[error("{side:?} record {record} has {found} descriptor values, expected {expected}")]
+    DescriptorLength {
+        side: MatchSide,
+        record: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("no serial number for image {image}")]
+    MissingSerialNumber { image: String },
+}
+
+/// What went wrong while parsing a match, without the offending input
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseErrorDetails {
+    /// nom error kind of the innermost failing parser
+    pub kind: ErrorKind,
+    /// image side of the failing record, `None` for the header and IP file records
+    pub side: Option<MatchSide>,
+    /// index of the failing record within its image side or IP file
+    pub record: Option<u64>,
+    /// name of the failing field, e.g. `ndesc` or `scale_lvl`
+    pub field: Option<&'static str>,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+impl fmt::Display for ParseErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.side, self.record) {
+            (Some(MatchSide::Image1), Some(record)) => write!(f, "image 1 record {}, ", record)?,
+            (Some(MatchSide::Image2), Some(record)) => write!(f, "image 2 record {}, ", record)?,
+            (None, Some(record)) => write!(f, "record {}, ", record)?,
+            _ if self.field.is_some() => write!(f, "header, ")?,
+            _ => {}
+        }
+        if let Some(field) = self.field {
+            write!(f, "field `{}`, ", field)?;
+        }
+        match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => write!(f, "expected {}, found {}", expected, found),
+            (Some(expected), None) => write!(f, "expected {}", expected),
+            _ => write!(f, "{}", self.kind.description()),
+        }
+    }
+}
+
+/// nom error type used by the match parsers
+///
+/// Keeps a reference to the remaining input where parsing failed instead of
+/// copying it, [`ASPMatchError`] only retains the corresponding offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchParseError<I> {
+    pub input: I,
+    pub details: ParseErrorDetails,
+}
+
+/// Result type of the match parsers
+pub type ParseResult<I, O> = IResult<I, O, MatchParseError<I>>;
+
+impl<I> MatchParseError<I> {
+    pub(crate) fn new(input: I, kind: ErrorKind) -> Self {
+        MatchParseError {
+            input,
+            details: ParseErrorDetails {
+                kind,
+                side: None,
+                record: None,
+                field: None,
+                expected: None,
+                found: None,
+            },
+        }
+    }
+
+    pub(crate) fn with_field(mut self, field: &'static str) -> Self {
+        self.details.field = Some(field);
+        self
+    }
+
+    pub(crate) fn with_expected_found<E: ToString, F: ToString>(
+        mut self,
+        expected: E,
+        found: F,
+    ) -> Self {
+        self.details.expected = Some(expected.to_string());
+        self.details.found = Some(found.to_string());
+        self
+    }
+
+    pub(crate) fn with_record(mut self, side: MatchSide, record: u64) -> Self {
+        self.details.side = Some(side);
+        self.details.record = Some(record);
+        self
+    }
+
+    /// Record of a single sided container, e.g. an IP file
+    pub(crate) fn with_index(mut self, record: u64) -> Self {
+        self.details.record = Some(record);
+        self
+    }
+}
+
+impl<I> ParseError<I> for MatchParseError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        MatchParseError::new(input, kind)
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        // keep the innermost error, it is the most precise one
+        other
+    }
+}
+
+impl<I> ContextError<I> for MatchParseError<I> {
+    fn add_context(_input: I, ctx: &'static str, mut other: Self) -> Self {
+        // contexts are field names, keep the innermost one
+        if other.details.field.is_none() {
+            other.details.field = Some(ctx);
+        }
+        other
+    }
+}
+
+/// Convert a binary parser error, `original` is the whole parsed input
+/// starting at byte `base_offset` of the match file, the error input must be
+/// a subslice of `original`
+pub(crate) fn binary_error(
+    original: &[u8],
+    base_offset: u64,
+    e: nom::Err<MatchParseError<&[u8]>>,
+) -> ASPMatchError {
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => ASPMatchError::BinaryParser {
+            offset: base_offset + original.offset(e.input) as u64,
+            details: e.details,
+        },
+        nom::Err::Incomplete(_) => ASPMatchError::BinaryParser {
+            offset: base_offset + original.len() as u64,
+            details: MatchParseError::new((), ErrorKind::Eof).details,
+        },
+    }
+}
+
+/// Convert a text parser error, `original` is the whole parsed input, the
+/// error input must be a subslice of `original`
+pub(crate) fn text_error(original: &str, e: nom::Err<MatchParseError<&str>>) -> ASPMatchError {
+    let (offset, details) = match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => (original.offset(e.input), e.details),
+        nom::Err::Incomplete(_) => (
+            original.len(),
+            MatchParseError::new((), ErrorKind::Eof).details,
+        ),
+    };
+    let before = &original[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    ASPMatchError::TextParser {
+        line: before.matches('\n').count() as u64 + 1,
+        column: before[line_start..].chars().count() as u64 + 1,
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::MatchSide;
+    use crate::error::{binary_error, text_error, ASPMatchError, MatchParseError};
+    use nom::error::ErrorKind;
+
+    #[test]
+    fn test_binary_error_offset() {
+        let original = [0u8; 10];
+        let e = MatchParseError::new(&original[4..], ErrorKind::Eof).with_field("ndesc");
+        match binary_error(&original, 16, nom::Err::Error(e)) {
+            ASPMatchError::BinaryParser { offset, details } => {
+                assert_eq!(offset, 20);
+                assert_eq!(details.field, Some("ndesc"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_text_error_line_column() {
+        let original = "1 1\n0 0 0\n0 x";
+        let e = MatchParseError::new(&original[12..], ErrorKind::Float)
+            .with_record(MatchSide::Image2, 0)
+            .with_field("y");
+        let e = text_error(original, nom::Err::Error(e));
+        match &e {
+            ASPMatchError::TextParser { line, column, .. } => {
+                assert_eq!((*line, *column), (3, 3));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(
+            e.to_string(),
+            "text parser error at line 3, column 3: image 2 record 0, field `y`, Float"
+        );
+    }
+
+    #[test]
+    fn test_details_expected_found() {
+        let e = MatchParseError::new((), ErrorKind::Verify)
+            .with_record(MatchSide::Image1, 3)
+            .with_field("desc")
+            .with_expected_found("2 values", "3 values");
+        assert_eq!(
+            e.details.to_string(),
+            "image 1 record 3, field `desc`, expected 2 values, found 3 values"
+        );
+    }
+
+    #[test]
+    fn test_io_error_display() {
+        let e = ASPMatchError::from(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        ));
+        assert_eq!(
+            e.to_string(),
+            "io error: stream did not contain valid UTF-8"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::data::{IPMatch, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::error::{binary_error, ASPMatchError};
+use crate::options::{ByteOrder, ParseOptions};
+use crate::parser::{
+    check_total_bytes, checked_record_count, ipmatch_with_options, limited_reader,
+    parse_text_match_str,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{digit1, line_ending, space1};
+use nom::combinator::eof;
+use nom::sequence::tuple;
+use nom::IResult;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+const HEADER_SIZE_IN_BYTES: usize = 2 * 8;
+
+/// Match file encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchFormat {
+    /// little endian binary, as written by ASP `ipmatch`
+    Binary,
+    /// ASCII text, as written by `parse_match_file.py`
+    Text,
+}
+
+/// Whether `records` binary records, read with their `ndesc`, fill exactly `input`
+///
+/// `records` must have been bounded by [`checked_record_count`], the walk is
+/// linear in it.
+pub(crate) fn records_fill_exactly(input: &[u8], records: u64, byte_order: ByteOrder) -> bool {
+    let fixed_size = MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+    let mut offset: usize = 0;
+    for _ in 0..records {
+        let record_end = match offset.checked_add(fixed_size) {
+            Some(end) if end <= input.len() => end,
+            _ => return false,
+        };
+        // ndesc is the last field of the fixed size part of the record
+        let ndesc = byte_order.u64_at(input, record_end - 8);
+        let desc_size = ndesc.saturating_mul(4);
+        match usize::try_from(desc_size)
+            .ok()
+            .and_then(|desc_size| record_end.checked_add(desc_size))
+        {
+            Some(end) if end <= input.len() => offset = end,
+            _ => return false,
+        }
+    }
+    offset == input.len()
+}
+
+/// Whether the header counts and the `ndesc` of every record add up to
+/// exactly the content size
+fn looks_like_binary(input: &[u8], byte_order: ByteOrder) -> bool {
+    if input.len() < HEADER_SIZE_IN_BYTES {
+        return false;
+    }
+    let size_1 = byte_order.u64_at(input, 0);
+    let size_2 = byte_order.u64_at(input, 8);
+    let records_input = &input[HEADER_SIZE_IN_BYTES..];
+    checked_record_count(size_1, size_2, records_input.len())
+        .is_some_and(|records| records_fill_exactly(records_input, records, byte_order))
+}
+
+/// Detect byte order of a binary match from the whole match content
+///
+/// The header counts are checked against the content size in both byte orders,
+/// little endian wins if both are plausible (e.g. empty match).
+///
+/// ```
+/// use aspmatch::{detect_byte_order, ByteOrder, IPMatch, IPRecord};
+/// let ipmatch = IPMatch { image_1: vec![IPRecord::default()], image_2: vec![] };
+/// let bytes = ipmatch.as_bytes(ByteOrder::BigEndian);
+/// assert_eq!(detect_byte_order(&bytes), Some(ByteOrder::BigEndian));
+/// ```
+pub fn detect_byte_order(input: &[u8]) -> Option<ByteOrder> {
+    [ByteOrder::LittleEndian, ByteOrder::BigEndian]
+        .into_iter()
+        .find(|byte_order| looks_like_binary(input, *byte_order))
+}
+
+/// Skip what lenient text parsing skips before the header, `None` if nothing
+/// else is left
+pub(crate) fn skip_leading_comments(mut input: &[u8]) -> Option<&[u8]> {
+    loop {
+        let start = input
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(input.len());
+        input = &input[start..];
+        match input.strip_prefix(b"#") {
+            Some(comment) => match comment.iter().position(|&b| b == b'\n') {
+                Some(end) => input = &comment[end..],
+                None => return None,
+            },
+            None => return Some(input),
+        }
+    }
+}
+
+fn looks_like_text(input: &[u8]) -> bool {
+    let input = match skip_leading_comments(input) {
+        Some(input) => input,
+        None => return false,
+    };
+    let header: IResult<&[u8], _> = tuple((
+        digit1,
+        space1,
+        digit1,
+        alt((line_ending, eof, space1, tag("#"))),
+    ))(input);
+    header.is_ok()
+}
+
+/// Detect match format from the whole match content
+///
+/// Binary: the two u64 counts (little or big endian) and the records they
+/// announce add up to exactly the content size.
+/// Text: content starts with an ASCII `N M` line, possibly after blank or `#`
+/// comment lines.
+///
+/// Returns `None` if neither format matches.
+///
+/// ```
+/// use aspmatch::{detect_match_format, IPMatch, MatchFormat};
+/// let ipmatch = IPMatch::default();
+/// assert_eq!(detect_match_format(&ipmatch.as_le_bytes()), Some(MatchFormat::Binary));
+/// assert_eq!(detect_match_format(ipmatch.as_text().as_bytes()), Some(MatchFormat::Text));
+/// assert_eq!(detect_match_format(b"not a match file"), None);
+/// ```
+pub fn detect_match_format(input: &[u8]) -> Option<MatchFormat> {
+    if detect_byte_order(input).is_some() {
+        Some(MatchFormat::Binary)
+    } else if looks_like_text(input) {
+        Some(MatchFormat::Text)
+    } else {
+        None
+    }
+}
+
+/// Parse IPMatch from reader in either format, assuming content fits in RAM
+///
+/// Binary byte order is detected as well.
+/// Returns detected format along with the parsed match.
+///
+/// ```
+/// use aspmatch::{parse_match_auto_from_reader, IPMatch, MatchFormat};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let text = IPMatch::default().as_text();
+/// let (format, parsed) = parse_match_auto_from_reader(text.as_bytes())?;
+/// assert_eq!(format, MatchFormat::Text);
+/// assert_eq!(parsed, IPMatch::default());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_match_auto_from_reader<R: Read>(
+    reader: R,
+) -> Result<(MatchFormat, IPMatch), ASPMatchError> {
+    parse_match_auto_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPMatch from reader in either format with options, assuming content
+/// fits in RAM
+///
+/// The detected byte order overrides `options.byte_order`.
+pub fn parse_match_auto_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<(MatchFormat, IPMatch), ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(reader), &options.limits);
+    let mut buf = vec![];
+    buf_reader.read_to_end(&mut buf)?;
+    // detection would fail on content cut by the limit
+    check_total_bytes(buf.as_slice(), buf.len() as u64, &options.limits)
+        .map_err(|e| binary_error(&buf, 0, e))?;
+    match detect_match_format(&buf) {
+        Some(MatchFormat::Binary) => {
+            let options = ParseOptions {
+                byte_order: detect_byte_order(&buf).unwrap_or_default(),
+                ..options.clone()
+            };
+            let (_, m) =
+                ipmatch_with_options(&buf, &options).map_err(|e| binary_error(&buf, 0, e))?;
+            Ok((MatchFormat::Binary, m))
+        }
+        Some(MatchFormat::Text) => {
+            let text = String::from_utf8(buf).map_err(|e| {
+                ASPMatchError::IO(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })?;
+            let m = parse_text_match_str(&text, options)?;
+            Ok((MatchFormat::Text, m))
+        }
+        None => Err(ASPMatchError::UnknownFormat),
+    }
+}
+
+/// Parse IPMatch from file at path in either format, assuming file fits in RAM
+pub fn parse_match_file_path_auto<P: AsRef<Path>>(
+    path: P,
+) -> Result<(MatchFormat, IPMatch), ASPMatchError> {
+    parse_match_file_path_auto_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPMatch from file at path in either format with options, assuming
+/// file fits in RAM
+pub fn parse_match_file_path_auto_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<(MatchFormat, IPMatch), ASPMatchError> {
+    let match_file = File::open(path)?;
+    parse_match_auto_from_reader_with_options(match_file, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MINIMUM_RECORD_SIZE_IN_BYTES};
+    use crate::format::{
+        detect_byte_order, detect_match_format, parse_match_auto_from_reader,
+        parse_match_auto_from_reader_with_options, MatchFormat,
+    };
+    use crate::{ASPMatchError, ByteOrder, ParseOptions, TextParseMode};
+
+    fn dummy_ipmatch() -> IPMatch {
+        IPMatch {
+            image_1: vec![IPRecord::default(); 2],
+            image_2: vec![IPRecord::default(); 2],
+        }
+    }
+
+    #[test]
+    fn test_detect_match_format() {
+        let ipmatch = dummy_ipmatch();
+        let bytes = ipmatch.as_le_bytes();
+        assert_eq!(detect_match_format(&bytes), Some(MatchFormat::Binary));
+        assert_eq!(
+            detect_match_format(ipmatch.as_text().as_bytes()),
+            Some(MatchFormat::Text)
+        );
+        // counts larger than what the content can hold
+        assert_eq!(detect_match_format(&bytes[..bytes.len() - 50]), None);
+        // counts small enough for the content size, but records do not fill it
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0u8; 4]);
+        assert_eq!(detect_match_format(&padded), None);
+        let mut bytes = IPMatch {
+            image_1: vec![IPRecord::default()],
+            image_2: vec![],
+        }
+        .as_le_bytes();
+        bytes.extend_from_slice(&[0u8; MINIMUM_RECORD_SIZE_IN_BYTES as usize]);
+        assert_eq!(detect_match_format(&bytes), None);
+        assert_eq!(detect_match_format(b"12 34\r\n"), Some(MatchFormat::Text));
+        assert_eq!(detect_match_format(b""), None);
+        assert_eq!(
+            detect_match_format(b"# comment\n\n  12 34 # sizes\n"),
+            Some(MatchFormat::Text)
+        );
+        assert_eq!(detect_match_format(b"# only a comment"), None);
+    }
+
+    #[test]
+    fn test_parse_match_auto_lenient_text() {
+        let expected = dummy_ipmatch();
+        let text = format!("# hand edited\n{}\n\n", expected.as_text());
+        assert!(parse_match_auto_from_reader(text.as_bytes()).is_err());
+        let options = ParseOptions {
+            text_mode: TextParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        let (format, parsed) =
+            parse_match_auto_from_reader_with_options(text.as_bytes(), &options).unwrap();
+        assert_eq!(format, MatchFormat::Text);
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_parse_match_auto_from_reader() {
+        let expected = dummy_ipmatch();
+        let bytes = expected.as_le_bytes();
+        let (format, parsed) = parse_match_auto_from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(format, MatchFormat::Binary);
+        assert_eq!(parsed, expected);
+        let text = expected.as_text();
+        let (format, parsed) = parse_match_auto_from_reader(text.as_bytes()).unwrap();
+        assert_eq!(format, MatchFormat::Text);
+        assert_eq!(parsed, expected);
+        assert!(matches!(
+            parse_match_auto_from_reader(&b"garbage"[..]),
+            Err(ASPMatchError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_detect_byte_order() {
+        let ipmatch = dummy_ipmatch();
+        let le_bytes = ipmatch.as_bytes(ByteOrder::LittleEndian);
+        let be_bytes = ipmatch.as_bytes(ByteOrder::BigEndian);
+        assert_eq!(detect_byte_order(&le_bytes), Some(ByteOrder::LittleEndian));
+        assert_eq!(detect_byte_order(&be_bytes), Some(ByteOrder::BigEndian));
+        assert_eq!(detect_byte_order(ipmatch.as_text().as_bytes()), None);
+        let (format, parsed) = parse_match_auto_from_reader(be_bytes.as_slice()).unwrap();
+        assert_eq!(format, MatchFormat::Binary);
+        assert_eq!(parsed, ipmatch);
+    }
+}
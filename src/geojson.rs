@@ -0,0 +1,230 @@
+use crate::data::{IPMatch, IPRecord};
+use crate::error::ASPMatchError;
+use crate::options::{DumpOptions, GeoJsonGeometry, GeoJsonOptions};
+use crate::parser::dump_to_path_atomically;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Shortest round trip representation, always with a fraction or an exponent
+/// (`1.0`, `1e20`), JSON has no NaN nor infinity: they are written as `null`,
+/// only used for properties, positions must be numbers
+fn write_number<W: Write>(w: &mut W, value: f32) -> io::Result<()> {
+    if value.is_finite() {
+        write!(w, "{:?}", value)
+    } else {
+        write!(w, "null")
+    }
+}
+
+fn write_point<W: Write>(w: &mut W, record: &IPRecord, options: &GeoJsonOptions) -> io::Result<()> {
+    let y = if options.flip_y { -record.y } else { record.y };
+    write!(w, "[")?;
+    write_number(w, record.x)?;
+    write!(w, ",")?;
+    write_number(w, y)?;
+    write!(w, "]")
+}
+
+fn write_feature<W: Write>(
+    w: &mut W,
+    index: usize,
+    (record_1, record_2): (&IPRecord, &IPRecord),
+    options: &GeoJsonOptions,
+) -> io::Result<()> {
+    let geometry = match options.geometry {
+        GeoJsonGeometry::LineString => "LineString",
+        GeoJsonGeometry::MultiPoint => "MultiPoint",
+    };
+    let finite = [record_1.x, record_1.y, record_2.x, record_2.y]
+        .iter()
+        .all(|value| value.is_finite());
+    if finite {
+        write!(
+            w,
+            r#"{{"type":"Feature","geometry":{{"type":"{}","coordinates":["#,
+            geometry
+        )?;
+        write_point(w, record_1, options)?;
+        write!(w, ",")?;
+        write_point(w, record_2, options)?;
+        write!(w, "]}}")?;
+    } else {
+        write!(w, r#"{{"type":"Feature","geometry":null"#)?;
+    }
+    write!(w, r#","properties":{{"pair":{}"#, index)?;
+    let properties = [
+        ("interest_1", record_1.interest),
+        ("scale_1", record_1.scale),
+        ("orientation_1", record_1.orientation),
+        ("interest_2", record_2.interest),
+        ("scale_2", record_2.scale),
+        ("orientation_2", record_2.orientation),
+        ("dx", record_2.x - record_1.x),
+        ("dy", record_2.y - record_1.y),
+    ];
+    for (name, value) in properties {
+        write!(w, r#","{}":"#, name)?;
+        write_number(w, value)?;
+    }
+    write!(w, "}}}}")
+}
+
+/// Dump IPMatch as a GeoJSON `FeatureCollection` in pixel space, one feature per pair
+///
+/// Each feature has a `LineString` or `MultiPoint` geometry (see
+/// [`GeoJsonOptions`]) joining the image 1 and image 2 points, and the
+/// properties `pair` (pair index), `interest_1`, `scale_1`, `orientation_1`,
+/// `interest_2`, `scale_2`, `orientation_2` and the displacement `dx`, `dy`
+/// from image 1 to image 2 (never flipped). Pairs with a NaN or infinite
+/// coordinate have a `null` geometry, GeoJSON positions must be numbers,
+/// non finite property values are written as `null`. Fails with
+/// [`ASPMatchError::UnequalSides`] if sides have different lengths.
+///
+/// ```
+/// use aspmatch::{dump_match_as_geojson, GeoJsonOptions, IPMatch, IPRecord};
+/// let a = IPRecord { x: 1.0, y: 2.0, ..IPRecord::default() };
+/// let b = IPRecord { x: 4.0, y: 6.0, ..IPRecord::default() };
+/// let ipmatch = IPMatch::from_pairs(vec![(a, b)]);
+/// let mut geojson = vec![];
+/// dump_match_as_geojson(&ipmatch, &mut geojson, &GeoJsonOptions::default()).unwrap();
+/// let geojson = String::from_utf8(geojson).unwrap();
+/// assert!(geojson.contains(r#""coordinates":[[1.0,2.0],[4.0,6.0]]"#));
+/// assert!(geojson.contains(r#""dx":3.0,"dy":4.0"#));
+/// ```
+pub fn dump_match_as_geojson<W: Write>(
+    ipmatch: &IPMatch,
+    w: &mut W,
+    options: &GeoJsonOptions,
+) -> Result<(), ASPMatchError> {
+    let pairs = ipmatch.pairs()?;
+    let mut w = BufWriter::new(w);
+    write!(w, r#"{{"type":"FeatureCollection","features":["#)?;
+    for (index, pair) in pairs.enumerate() {
+        if index > 0 {
+            write!(w, ",")?;
+        }
+        writeln!(w)?;
+        write_feature(&mut w, index, pair, options)?;
+    }
+    writeln!(w, "\n]}}")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Dump IPMatch as GeoJSON to file at path, replacing it atomically
+pub fn dump_match_as_geojson_to_path<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_match_as_geojson_to_path_with_options(
+        ipmatch,
+        path,
+        &GeoJsonOptions::default(),
+        &DumpOptions::default(),
+    )
+}
+
+/// Dump IPMatch as GeoJSON with `options` to file at path atomically, following `dump_options`
+pub fn dump_match_as_geojson_to_path_with_options<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+    options: &GeoJsonOptions,
+    dump_options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    // the pairing is checked before replacing the file
+    ipmatch.pair_count()?;
+    dump_to_path_atomically(path, dump_options, |file| {
+        dump_match_as_geojson(ipmatch, file, options)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord};
+    use crate::error::ASPMatchError;
+    use crate::geojson::{dump_match_as_geojson, dump_match_as_geojson_to_path_with_options};
+    use crate::options::{DumpOptions, GeoJsonGeometry, GeoJsonOptions};
+    use serde_json::{json, Value};
+
+    fn dummy_ipmatch() -> IPMatch {
+        let record = |x: f32, y: f32| IPRecord {
+            x,
+            y,
+            interest: 0.5,
+            scale: 2.0,
+            orientation: -1.5,
+            ..IPRecord::default()
+        };
+        IPMatch::from_pairs(vec![
+            (record(1.0, 2.0), record(1.5, 4.0)),
+            (record(10.0, 20.0), record(f32::NAN, 18.0)),
+        ])
+    }
+
+    fn geojson(ipmatch: &IPMatch, options: &GeoJsonOptions) -> Value {
+        let mut buffer = vec![];
+        dump_match_as_geojson(ipmatch, &mut buffer, options).unwrap();
+        serde_json::from_slice(&buffer).unwrap()
+    }
+
+    #[test]
+    fn test_geojson_line_string() {
+        let value = geojson(&dummy_ipmatch(), &GeoJsonOptions::default());
+        assert_eq!(value["type"], "FeatureCollection");
+        let features = value["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(
+            features[0],
+            json!({
+                "type": "Feature",
+                "geometry": {"type": "LineString", "coordinates": [[1.0, 2.0], [1.5, 4.0]]},
+                "properties": {
+                    "pair": 0,
+                    "interest_1": 0.5, "scale_1": 2.0, "orientation_1": -1.5,
+                    "interest_2": 0.5, "scale_2": 2.0, "orientation_2": -1.5,
+                    "dx": 0.5, "dy": 2.0
+                }
+            })
+        );
+        // non finite values are not valid JSON, positions must be numbers
+        assert_eq!(features[1]["geometry"], Value::Null);
+        assert_eq!(features[1]["properties"]["pair"], 1);
+        assert_eq!(features[1]["properties"]["dx"], Value::Null);
+        assert_eq!(features[1]["properties"]["dy"], -2.0);
+    }
+
+    #[test]
+    fn test_geojson_options() {
+        let options = GeoJsonOptions {
+            geometry: GeoJsonGeometry::MultiPoint,
+            flip_y: true,
+        };
+        let value = geojson(&dummy_ipmatch(), &options);
+        let feature = &value["features"][0];
+        assert_eq!(
+            feature["geometry"],
+            json!({"type": "MultiPoint", "coordinates": [[1.0, -2.0], [1.5, -4.0]]})
+        );
+        assert_eq!(feature["properties"]["dy"], 2.0);
+        let empty = geojson(&IPMatch::default(), &options);
+        assert_eq!(empty, json!({"type": "FeatureCollection", "features": []}));
+    }
+
+    #[test]
+    fn test_geojson_unequal_sides() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("match.geojson");
+        std::fs::write(&path, "previous").unwrap();
+        let mut ipmatch = dummy_ipmatch();
+        ipmatch.image_2.pop();
+        let result = dump_match_as_geojson_to_path_with_options(
+            &ipmatch,
+            &path,
+            &GeoJsonOptions::default(),
+            &DumpOptions::default(),
+        );
+        assert!(matches!(result, Err(ASPMatchError::UnequalSides { .. })));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous");
+    }
+}
@@ -0,0 +1,501 @@
+use crate::data::{IPFile, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::error::{binary_error, text_error, ASPMatchError, MatchParseError, ParseResult};
+use crate::format::{records_fill_exactly, skip_leading_comments, MatchFormat};
+use crate::options::{
+    ByteOrder, DumpOptions, ParseLimits, ParseOptions, TextParseMode, TextWriteOptions,
+};
+use crate::parser::{
+    binary_records, check_limit, check_total_bytes, checked_record_count, dump_to_path_atomically,
+    end_of_text, lenient_lines, lenient_records, limited_reader, read_text, text_records,
+    usize_count,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{digit1, line_ending, space0};
+use nom::combinator::{all_consuming, eof};
+use nom::error::{context, ErrorKind};
+use nom::number::complete as number;
+use nom::sequence::{terminated, tuple};
+use nom::IResult;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Check the IP file record count fits in memory and `limits`
+fn ip_file_record_count<I: Copy>(
+    input: I,
+    size: u64,
+    limits: &ParseLimits,
+) -> Result<u64, nom::Err<MatchParseError<I>>> {
+    usize_count(input, "size", size)?;
+    check_limit(input, "size", size, limits.max_records)?;
+    Ok(size)
+}
+
+/// Parse IPFile from byte slice assuming little endianness
+///
+/// Returns remaining bytes
+///
+/// ```
+/// use aspmatch::{ipfile, IPFile, IPRecord};
+/// let ip_file = IPFile { records: vec![IPRecord::default(); 2] };
+/// let bytes = ip_file.as_le_bytes();
+/// let (remaining_bytes, parsed) = ipfile(bytes.as_slice()).unwrap();
+/// assert!(remaining_bytes.is_empty());
+/// assert_eq!(parsed, ip_file);
+/// ```
+pub fn ipfile(input: &[u8]) -> ParseResult<&[u8], IPFile> {
+    ipfile_with_options(input, &ParseOptions::default())
+}
+
+/// Parse IPFile from byte slice in `options.byte_order`
+///
+/// Returns remaining bytes
+pub fn ipfile_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> ParseResult<&'a [u8], IPFile> {
+    let e = options.byte_order.endianness();
+    check_total_bytes(input, input.len() as u64, &options.limits)?;
+    let (i, size) = context("size", number::u64(e))(input)?;
+    let size = ip_file_record_count(input, size, &options.limits)?;
+    // make sure size is reasonably sized
+    let records = checked_record_count(size, 0, i.len()).ok_or_else(|| {
+        let max_records = i.len() / MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+        nom::Err::Error(
+            MatchParseError::new(input, ErrorKind::Verify)
+                .with_field("size")
+                .with_expected_found(
+                    format!("at most {} records", max_records),
+                    format!("{} records", size),
+                ),
+        )
+    })?;
+    let (i, records) = binary_records(i, records, options, |e, index| e.with_index(index))?;
+    Ok((i, IPFile { records }))
+}
+
+/// Parse text header `size`
+fn ipfile_text_header<'a>(input: &'a str, limits: &ParseLimits) -> ParseResult<&'a str, u64> {
+    check_total_bytes(input, input.len() as u64, limits)?;
+    let (i, size) = context(
+        "size",
+        terminated(nom::character::complete::u64, line_ending),
+    )(input)?;
+    let records = ip_file_record_count(input, size, limits)?;
+    Ok((i, records))
+}
+
+/// Parse IPFile from text
+///
+/// Returns remaining text
+///
+/// ```
+/// use aspmatch::{ipfile_text, IPFile, IPRecord};
+/// let ip_file = IPFile { records: vec![IPRecord::default(); 2] };
+/// let text = ip_file.as_text();
+/// let (_, parsed) = ipfile_text(&text).unwrap();
+/// assert_eq!(parsed, ip_file);
+/// ```
+pub fn ipfile_text(input: &str) -> ParseResult<&str, IPFile> {
+    ipfile_text_strict(input, &ParseOptions::default())
+}
+
+fn ipfile_text_strict<'a>(input: &'a str, options: &ParseOptions) -> ParseResult<&'a str, IPFile> {
+    let (i, records) = ipfile_text_header(input, &options.limits)?;
+    let (i, records) = text_records(i, records, options, |e, index| e.with_index(index))?;
+    Ok((i, IPFile { records }))
+}
+
+fn ipfile_text_lenient<'a>(input: &'a str, options: &ParseOptions) -> ParseResult<&'a str, IPFile> {
+    check_total_bytes(input, input.len() as u64, &options.limits)?;
+    let mut lines = lenient_lines(input);
+    let header = lines.next().unwrap_or(&input[input.len()..]);
+    let (_, size) = all_consuming(context("size", nom::character::complete::u64))(header)?;
+    let size = ip_file_record_count(header, size, &options.limits)?;
+    let records = lenient_records(input, lines, size, options, |e, index| e.with_index(index))?;
+    Ok((&input[input.len()..], IPFile { records }))
+}
+
+/// Parse IPFile from text in `options.text_mode`
+///
+/// Like [`crate::ipmatch_text_with_options`], the whole input must be consumed.
+///
+/// ```
+/// use aspmatch::{ipfile_text_with_options, IPRecord, ParseOptions, TextParseMode};
+/// let text = "# ipfind output\n1\n0 0 0 0 0 0 0 0 0 0 0\n\n";
+/// let options = ParseOptions { text_mode: TextParseMode::Lenient, ..ParseOptions::default() };
+/// let (_, parsed) = ipfile_text_with_options(text, &options).unwrap();
+/// assert_eq!(parsed.records, vec![IPRecord::default()]);
+/// ```
+pub fn ipfile_text_with_options<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPFile> {
+    match options.text_mode {
+        TextParseMode::Strict => {
+            let (i, ip_file) = ipfile_text_strict(input, options)?;
+            Ok((end_of_text(i)?.0, ip_file))
+        }
+        TextParseMode::Lenient => ipfile_text_lenient(input, options),
+    }
+}
+
+fn parse_text_ip_file_str(text: &str, options: &ParseOptions) -> Result<IPFile, ASPMatchError> {
+    let (_, ip_file) = ipfile_text_with_options(text, options).map_err(|e| text_error(text, e))?;
+    Ok(ip_file)
+}
+
+/// Parse IPFile from binary reader assuming content fits in RAM
+pub fn parse_binary_ip_file_from_reader<R: Read>(reader: R) -> Result<IPFile, ASPMatchError> {
+    parse_binary_ip_file_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPFile from binary reader with options assuming content fits in RAM
+pub fn parse_binary_ip_file_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<IPFile, ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(reader), &options.limits);
+    let mut buf = vec![];
+    buf_reader.read_to_end(&mut buf)?;
+    let (_, ip_file) = ipfile_with_options(&buf, options).map_err(|e| binary_error(&buf, 0, e))?;
+    Ok(ip_file)
+}
+
+/// Parse IPFile from binary file at path, e.g. a `.vwip` file, assuming file fits in RAM
+pub fn parse_binary_ip_file_path<P: AsRef<Path>>(path: P) -> Result<IPFile, ASPMatchError> {
+    parse_binary_ip_file_path_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPFile from binary file at path with options, assuming file fits in RAM
+pub fn parse_binary_ip_file_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<IPFile, ASPMatchError> {
+    parse_binary_ip_file_from_reader_with_options(File::open(path)?, options)
+}
+
+/// Parse IPFile from text reader assuming content fits in RAM
+pub fn parse_text_ip_file_from_reader<R: Read>(reader: R) -> Result<IPFile, ASPMatchError> {
+    parse_text_ip_file_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPFile from text reader in `options.text_mode`, assuming content fits in RAM
+pub fn parse_text_ip_file_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<IPFile, ASPMatchError> {
+    let text = read_text(reader, &options.limits)?;
+    parse_text_ip_file_str(&text, options)
+}
+
+/// Parse IPFile from text file at path, assuming file fits in RAM
+pub fn parse_text_ip_file_path<P: AsRef<Path>>(path: P) -> Result<IPFile, ASPMatchError> {
+    parse_text_ip_file_path_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPFile from text file at path with options, assuming file fits in RAM
+pub fn parse_text_ip_file_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<IPFile, ASPMatchError> {
+    parse_text_ip_file_from_reader_with_options(File::open(path)?, options)
+}
+
+fn ip_file_byte_order(input: &[u8]) -> Option<ByteOrder> {
+    [ByteOrder::LittleEndian, ByteOrder::BigEndian]
+        .into_iter()
+        .find(|byte_order| {
+            if input.len() < 8 {
+                return false;
+            }
+            let size = byte_order.u64_at(input, 0);
+            let records_input = &input[8..];
+            checked_record_count(size, 0, records_input.len())
+                .is_some_and(|records| records_fill_exactly(records_input, records, *byte_order))
+        })
+}
+
+fn looks_like_ip_text(input: &[u8]) -> bool {
+    let input = match skip_leading_comments(input) {
+        Some(input) => input,
+        None => return false,
+    };
+    let header: IResult<&[u8], _> =
+        tuple((digit1, space0, alt((line_ending, eof, tag("#")))))(input);
+    header.is_ok()
+}
+
+/// Detect IP file format from the whole content
+///
+/// Binary: the u64 count (little or big endian) and the records it announces
+/// add up to exactly the content size.
+/// Text: content starts with an ASCII `N` line, possibly after blank or `#`
+/// comment lines.
+///
+/// ```
+/// use aspmatch::{detect_ip_file_format, IPFile, MatchFormat};
+/// let ip_file = IPFile::default();
+/// assert_eq!(detect_ip_file_format(&ip_file.as_le_bytes()), Some(MatchFormat::Binary));
+/// assert_eq!(detect_ip_file_format(ip_file.as_text().as_bytes()), Some(MatchFormat::Text));
+/// assert_eq!(detect_ip_file_format(b"not an ip file"), None);
+/// ```
+pub fn detect_ip_file_format(input: &[u8]) -> Option<MatchFormat> {
+    if ip_file_byte_order(input).is_some() {
+        Some(MatchFormat::Binary)
+    } else if looks_like_ip_text(input) {
+        Some(MatchFormat::Text)
+    } else {
+        None
+    }
+}
+
+/// Parse IPFile from reader in either format, assuming content fits in RAM
+///
+/// Binary byte order is detected as well.
+/// Returns detected format along with the parsed IP file.
+pub fn parse_ip_file_auto_from_reader<R: Read>(
+    reader: R,
+) -> Result<(MatchFormat, IPFile), ASPMatchError> {
+    parse_ip_file_auto_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPFile from reader in either format with options, assuming content
+/// fits in RAM
+///
+/// The detected byte order overrides `options.byte_order`.
+pub fn parse_ip_file_auto_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<(MatchFormat, IPFile), ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(reader), &options.limits);
+    let mut buf = vec![];
+    buf_reader.read_to_end(&mut buf)?;
+    // detection would fail on content cut by the limit
+    check_total_bytes(buf.as_slice(), buf.len() as u64, &options.limits)
+        .map_err(|e| binary_error(&buf, 0, e))?;
+    match detect_ip_file_format(&buf) {
+        Some(MatchFormat::Binary) => {
+            let options = ParseOptions {
+                byte_order: ip_file_byte_order(&buf).unwrap_or_default(),
+                ..options.clone()
+            };
+            let (_, ip_file) =
+                ipfile_with_options(&buf, &options).map_err(|e| binary_error(&buf, 0, e))?;
+            Ok((MatchFormat::Binary, ip_file))
+        }
+        Some(MatchFormat::Text) => {
+            let text = String::from_utf8(buf).map_err(|e| {
+                ASPMatchError::IO(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })?;
+            Ok((MatchFormat::Text, parse_text_ip_file_str(&text, options)?))
+        }
+        None => Err(ASPMatchError::UnknownFormat),
+    }
+}
+
+/// Parse IPFile from file at path in either format, assuming file fits in RAM
+pub fn parse_ip_file_path_auto<P: AsRef<Path>>(
+    path: P,
+) -> Result<(MatchFormat, IPFile), ASPMatchError> {
+    parse_ip_file_path_auto_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPFile from file at path in either format with options, assuming
+/// file fits in RAM
+pub fn parse_ip_file_path_auto_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<(MatchFormat, IPFile), ASPMatchError> {
+    parse_ip_file_auto_from_reader_with_options(File::open(path)?, options)
+}
+
+/// Dump IPFile as binary
+pub fn dump_ip_file_as_binary<W: Write>(ip_file: &IPFile, w: &mut W) -> Result<(), ASPMatchError> {
+    dump_ip_file_as_binary_with_byte_order(ip_file, w, ByteOrder::LittleEndian)
+}
+
+/// Dump IPFile as binary in given byte order
+pub fn dump_ip_file_as_binary_with_byte_order<W: Write>(
+    ip_file: &IPFile,
+    w: &mut W,
+    byte_order: ByteOrder,
+) -> Result<(), ASPMatchError> {
+    let mut w = BufWriter::new(w);
+    ip_file.write_bytes(&mut w, byte_order)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Dump IPFile as text
+pub fn dump_ip_file_as_text<W: Write>(ip_file: &IPFile, w: &mut W) -> Result<(), ASPMatchError> {
+    dump_ip_file_as_text_with_options(ip_file, w, &TextWriteOptions::default())
+}
+
+/// Dump IPFile as text, formatting floats according to `options`
+pub fn dump_ip_file_as_text_with_options<W: Write>(
+    ip_file: &IPFile,
+    w: &mut W,
+    options: &TextWriteOptions,
+) -> Result<(), ASPMatchError> {
+    let mut w = BufWriter::new(w);
+    ip_file.write_text_with_options(&mut w, options)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Dump IPFile as binary to file at path, replacing it atomically
+pub fn dump_ip_file_as_binary_to_path<P: AsRef<Path>>(
+    ip_file: &IPFile,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_ip_file_as_binary_to_path_with_options(ip_file, path, &DumpOptions::default())
+}
+
+/// Dump IPFile as binary to file at path atomically, following `options`
+pub fn dump_ip_file_as_binary_to_path_with_options<P: AsRef<Path>>(
+    ip_file: &IPFile,
+    path: P,
+    options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    dump_to_path_atomically(path, options, |file| dump_ip_file_as_binary(ip_file, file))
+}
+
+/// Dump IPFile as text to file at path, replacing it atomically
+pub fn dump_ip_file_as_text_to_path<P: AsRef<Path>>(
+    ip_file: &IPFile,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_ip_file_as_text_to_path_with_options(ip_file, path, &DumpOptions::default())
+}
+
+/// Dump IPFile as text to file at path atomically, following `options`
+pub fn dump_ip_file_as_text_to_path_with_options<P: AsRef<Path>>(
+    ip_file: &IPFile,
+    path: P,
+    options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    dump_to_path_atomically(path, options, |file| {
+        dump_ip_file_as_text_with_options(ip_file, file, &options.text)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPFile, IPRecord};
+    use crate::error::ASPMatchError;
+    use crate::format::MatchFormat;
+    use crate::ipfile::{
+        detect_ip_file_format, dump_ip_file_as_binary_to_path_with_options,
+        dump_ip_file_as_text_to_path, ipfile, ipfile_text_with_options, ipfile_with_options,
+        parse_binary_ip_file_path, parse_ip_file_auto_from_reader, parse_ip_file_path_auto,
+        parse_text_ip_file_from_reader,
+    };
+    use crate::options::{ByteOrder, DumpOptions, ParseLimits, ParseOptions, TextParseMode};
+
+    fn dummy_ip_file() -> IPFile {
+        let record = |x: f32| IPRecord {
+            x,
+            y: 2.0 * x,
+            desc: vec![x; 3],
+            ..IPRecord::default()
+        };
+        IPFile {
+            records: (0..4).map(|i| record(i as f32 + 0.5)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_ip_file_round_trip() {
+        let ip_file = dummy_ip_file();
+        for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let bytes = ip_file.as_bytes(byte_order);
+            assert_eq!(detect_ip_file_format(&bytes), Some(MatchFormat::Binary));
+            let (format, parsed) = parse_ip_file_auto_from_reader(bytes.as_slice()).unwrap();
+            assert_eq!((format, parsed), (MatchFormat::Binary, ip_file.clone()));
+        }
+        let text = ip_file.as_text();
+        assert_eq!(
+            parse_text_ip_file_from_reader(text.as_bytes()).unwrap(),
+            ip_file
+        );
+        let (format, parsed) = parse_ip_file_auto_from_reader(text.as_bytes()).unwrap();
+        assert_eq!((format, parsed), (MatchFormat::Text, ip_file));
+        let empty = IPFile::default();
+        let (_, parsed) = parse_ip_file_auto_from_reader(empty.as_text().as_bytes()).unwrap();
+        assert_eq!(parsed, empty);
+    }
+
+    #[test]
+    fn test_detect_ip_file_format_exact_size() {
+        let mut bytes = dummy_ip_file().as_le_bytes();
+        bytes.push(0);
+        assert_eq!(detect_ip_file_format(&bytes), None);
+        // a small leading count is not enough
+        let mut bytes = 1u64.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0xff; 100]);
+        assert_eq!(detect_ip_file_format(&bytes), None);
+    }
+
+    #[test]
+    fn test_ip_file_errors() {
+        let bytes = dummy_ip_file().as_le_bytes();
+        let e = ipfile(&bytes[..bytes.len() - 1]).unwrap_err();
+        match e {
+            nom::Err::Error(e) => {
+                assert_eq!(e.details.side, None);
+                assert_eq!(e.details.record, Some(3));
+                assert_eq!(e.details.field, Some("desc"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_records: 3,
+                ..ParseLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert!(ipfile_with_options(&bytes, &options).is_err());
+
+        let text = dummy_ip_file().as_text().replacen("4\n", "3\n", 1);
+        assert!(ipfile_text_with_options(&text, &ParseOptions::default()).is_err());
+        let text = "2\n0 0 0 0 0 0 0 0 0 0 0 \n0 0 x 0 0 0 0 0 0 0 0 \n";
+        let lenient = ParseOptions {
+            text_mode: TextParseMode::Lenient,
+            ..ParseOptions::default()
+        };
+        match parse_text_ip_file_from_reader(text.as_bytes()).unwrap_err() {
+            ASPMatchError::TextParser {
+                line,
+                column,
+                details,
+            } => {
+                assert_eq!((line, column), (3, 5));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.to_string(), "record 1, field `xi`, Digit");
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(ipfile_text_with_options(text, &lenient).is_err());
+    }
+
+    #[test]
+    fn test_ip_file_to_path() {
+        let directory = tempfile::tempdir().unwrap();
+        let ip_file = dummy_ip_file();
+        let binary = directory.path().join("image.vwip");
+        let options = DumpOptions {
+            overwrite: false,
+            ..DumpOptions::default()
+        };
+        dump_ip_file_as_binary_to_path_with_options(&ip_file, &binary, &options).unwrap();
+        assert!(dump_ip_file_as_binary_to_path_with_options(&ip_file, &binary, &options).is_err());
+        assert_eq!(parse_binary_ip_file_path(&binary).unwrap(), ip_file);
+        let text = directory.path().join("image.vwip.txt");
+        dump_ip_file_as_text_to_path(&ip_file, &text).unwrap();
+        let (format, parsed) = parse_ip_file_path_auto(&text).unwrap();
+        assert_eq!((format, parsed), (MatchFormat::Text, ip_file));
+    }
+}
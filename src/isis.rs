@@ -0,0 +1,825 @@
+use crate::data::IPMatch;
+use crate::error::{text_error, ASPMatchError, MatchParseError};
+use crate::options::{DumpOptions, IsisOptions};
+use crate::parser::dump_to_path_atomically;
+use crate::tracks::{Observation, Track, TrackSet};
+use nom::combinator::all_consuming;
+use nom::error::ErrorKind;
+use nom::number::complete::double;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Control measure: serial number, x, y
+type Measure<'a> = (&'a str, f32, f32);
+
+fn pixel_offset(options: &IsisOptions) -> f64 {
+    if options.one_based {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SS` UTC
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn write_value<W: Write>(w: &mut W, value: &str) -> io::Result<()> {
+    let quoted = value.is_empty()
+        || value.contains("/*")
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || "\"'=(){}[],;#<>".contains(c));
+    if value.contains('"') {
+        // PVL quoted strings cannot contain double quotes, changing them
+        // would break serial numbers
+        let message = format!("PVL values cannot contain '\"': {}", value);
+        Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+    } else if quoted {
+        write!(w, "\"{}\"", value)
+    } else {
+        write!(w, "{}", value)
+    }
+}
+
+fn write_control_network<'a, W, I>(
+    w: &mut W,
+    points: I,
+    options: &IsisOptions,
+) -> Result<(), ASPMatchError>
+where
+    W: Write,
+    I: Iterator<Item = Vec<Measure<'a>>>,
+{
+    let mut w = BufWriter::new(w);
+    let now = timestamp(SystemTime::now());
+    let header = [
+        ("NetworkId", &options.network_id),
+        ("TargetName", &options.target_name),
+        ("UserName", &options.user_name),
+        ("Created", &now),
+        ("LastModified", &now),
+        ("Description", &options.description),
+    ];
+    writeln!(w, "Object = ControlNetwork")?;
+    for (keyword, value) in header {
+        write!(w, "  {:<12} = ", keyword)?;
+        write_value(&mut w, value)?;
+        writeln!(w)?;
+    }
+    writeln!(w, "  Version      = 5")?;
+    let offset = pixel_offset(options);
+    for (index, measures) in points.enumerate() {
+        writeln!(w)?;
+        writeln!(w, "  Object = ControlPoint")?;
+        writeln!(w, "    PointType = Free")?;
+        write!(w, "    PointId   = ")?;
+        write_value(&mut w, &format!("{}{}", options.point_id_prefix, index))?;
+        writeln!(w)?;
+        for (serial_number, x, y) in measures {
+            writeln!(w)?;
+            writeln!(w, "    Group = ControlMeasure")?;
+            write!(w, "      SerialNumber = ")?;
+            write_value(&mut w, serial_number)?;
+            writeln!(w)?;
+            writeln!(w, "      MeasureType  = Candidate")?;
+            // f64 keeps the offset coordinates exact
+            writeln!(w, "      Sample       = {:?}", x as f64 + offset)?;
+            writeln!(w, "      Line         = {:?}", y as f64 + offset)?;
+            writeln!(w, "    End_Group")?;
+        }
+        writeln!(w, "  End_Object")?;
+    }
+    writeln!(w, "End_Object")?;
+    writeln!(w, "End")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Export IPMatch as an ISIS PVL control network, one control point per pair
+///
+/// Control measures of image 1 and image 2 have serial numbers
+/// `serial_number_1` and `serial_number_2`, see [`IsisOptions`] for the
+/// pixel convention. Fails with an `InvalidInput` io error if a serial number
+/// or an option value contains `"`, PVL has no way to write it.
+///
+/// ```
+/// use aspmatch::{dump_match_as_isis_pvl, IPMatch, IPRecord, IsisOptions};
+/// let record = |x: f32, y: f32| IPRecord { x, y, ..IPRecord::default() };
+/// let ipmatch = IPMatch::from_pairs(vec![(record(1.0, 2.0), record(4.5, 6.0))]);
+/// let mut pvl = vec![];
+/// dump_match_as_isis_pvl(&ipmatch, "CTX/1", "CTX/2", &mut pvl, &IsisOptions::default()).unwrap();
+/// let pvl = String::from_utf8(pvl).unwrap();
+/// assert!(pvl.contains("SerialNumber = CTX/2"));
+/// assert!(pvl.contains("Sample       = 5.5"));
+/// ```
+pub fn dump_match_as_isis_pvl<W: Write>(
+    ipmatch: &IPMatch,
+    serial_number_1: &str,
+    serial_number_2: &str,
+    w: &mut W,
+    options: &IsisOptions,
+) -> Result<(), ASPMatchError> {
+    let points = ipmatch.pairs()?.map(|(record_1, record_2)| {
+        vec![
+            (serial_number_1, record_1.x, record_1.y),
+            (serial_number_2, record_2.x, record_2.y),
+        ]
+    });
+    write_control_network(w, points, options)
+}
+
+/// Export IPMatch as an ISIS PVL control network to file at path, replacing it atomically
+pub fn dump_match_as_isis_pvl_to_path<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    serial_number_1: &str,
+    serial_number_2: &str,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_match_as_isis_pvl_to_path_with_options(
+        ipmatch,
+        serial_number_1,
+        serial_number_2,
+        path,
+        &IsisOptions::default(),
+        &DumpOptions::default(),
+    )
+}
+
+/// Export IPMatch as an ISIS PVL control network with `options` to file at path atomically,
+/// following `dump_options`
+pub fn dump_match_as_isis_pvl_to_path_with_options<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    serial_number_1: &str,
+    serial_number_2: &str,
+    path: P,
+    options: &IsisOptions,
+    dump_options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    // the pairing is checked before replacing the file
+    ipmatch.pair_count()?;
+    dump_to_path_atomically(path, dump_options, |file| {
+        dump_match_as_isis_pvl(ipmatch, serial_number_1, serial_number_2, file, options)
+    })
+}
+
+/// Measures of the non conflicting tracks
+fn track_points<'a>(
+    track_set: &'a TrackSet,
+    serial_numbers: &'a HashMap<String, String>,
+) -> Result<Vec<Vec<Measure<'a>>>, ASPMatchError> {
+    let measure = |observation: &Observation| {
+        track_set
+            .images
+            .get(observation.image)
+            .and_then(|image| serial_numbers.get(image))
+            .map(|serial_number| (serial_number.as_str(), observation.x, observation.y))
+            .ok_or_else(|| ASPMatchError::MissingSerialNumber {
+                image: track_set
+                    .images
+                    .get(observation.image)
+                    .cloned()
+                    .unwrap_or_else(|| observation.image.to_string()),
+            })
+    };
+    track_set
+        .tracks
+        .iter()
+        .filter(|track| !track.is_conflicting())
+        .map(|track| track.observations.iter().map(measure).collect())
+        .collect()
+}
+
+/// Export a track set as an ISIS PVL control network
+///
+/// Each track is a control point and each observation a control measure,
+/// `serial_numbers` maps [`TrackSet::images`] names to ISIS serial numbers.
+/// Conflicting tracks are skipped, ISIS does not allow two measures of the
+/// same image in a control point. Fails with
+/// [`ASPMatchError::MissingSerialNumber`] before writing anything if an
+/// image has no serial number, and with an `InvalidInput` io error if a
+/// serial number or an option value contains `"`.
+pub fn dump_tracks_as_isis_pvl<W: Write>(
+    track_set: &TrackSet,
+    serial_numbers: &HashMap<String, String>,
+    w: &mut W,
+    options: &IsisOptions,
+) -> Result<(), ASPMatchError> {
+    let points = track_points(track_set, serial_numbers)?;
+    write_control_network(w, points.into_iter(), options)
+}
+
+/// Export a track set as an ISIS PVL control network to file at path, replacing it atomically
+pub fn dump_tracks_as_isis_pvl_to_path<P: AsRef<Path>>(
+    track_set: &TrackSet,
+    serial_numbers: &HashMap<String, String>,
+    path: P,
+) -> Result<(), ASPMatchError> {
+    dump_tracks_as_isis_pvl_to_path_with_options(
+        track_set,
+        serial_numbers,
+        path,
+        &IsisOptions::default(),
+        &DumpOptions::default(),
+    )
+}
+
+/// Export a track set as an ISIS PVL control network with `options` to file at path atomically,
+/// following `dump_options`
+pub fn dump_tracks_as_isis_pvl_to_path_with_options<P: AsRef<Path>>(
+    track_set: &TrackSet,
+    serial_numbers: &HashMap<String, String>,
+    path: P,
+    options: &IsisOptions,
+    dump_options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    let points = track_points(track_set, serial_numbers)?;
+    dump_to_path_atomically(path, dump_options, |file| {
+        write_control_network(file, points.into_iter(), options)
+    })
+}
+
+/// PVL block being parsed
+enum Block<'a> {
+    Network,
+    Point {
+        index: u64,
+        ignore: bool,
+        observations: Vec<Observation>,
+    },
+    Measure {
+        serial_number: Option<&'a str>,
+        sample: Option<f64>,
+        line: Option<f64>,
+        ignore: bool,
+    },
+    Object,
+    Group,
+}
+
+impl Block<'_> {
+    fn is_group(&self) -> bool {
+        matches!(self, Block::Measure { .. } | Block::Group)
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn is_true(value: &str) -> bool {
+    ["true", "yes"]
+        .iter()
+        .any(|t| unquote(value).eq_ignore_ascii_case(t))
+}
+
+/// Quoted, parenthesized or braced value spanning several lines
+struct Continuation {
+    open: char,
+    close: char,
+    depth: usize,
+    quoted: bool,
+}
+
+impl Continuation {
+    /// `None` unless `value` opens a quote, parenthesis or brace it does not close
+    fn new(value: &str) -> Option<Self> {
+        let open = value.chars().next()?;
+        let close = match open {
+            '"' => '"',
+            '(' => ')',
+            '{' => '}',
+            _ => return None,
+        };
+        let mut continuation = Continuation {
+            open,
+            close,
+            depth: 0,
+            quoted: false,
+        };
+        (!continuation.closes(value)).then_some(continuation)
+    }
+
+    /// Whether `line` closes the opening delimiter, delimiters within quotes
+    /// and nested ones are skipped
+    fn closes(&mut self, line: &str) -> bool {
+        for c in line.chars() {
+            if c == '"' {
+                self.quoted = !self.quoted;
+            } else if self.quoted {
+                continue;
+            } else if c == self.open {
+                self.depth += 1;
+            } else if c == self.close {
+                self.depth = self.depth.saturating_sub(1);
+            }
+            if !self.quoted && self.depth == 0 {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Parse a track set from an ISIS PVL control network
+///
+/// Control points become tracks and control measures observations,
+/// [`TrackSet::images`] are the measure serial numbers. Ignored points and
+/// measures are skipped. See [`IsisOptions`] for the pixel convention, use
+/// [`TrackSet::pairwise_matches`] to get match files back.
+///
+/// ```
+/// use aspmatch::{parse_isis_pvl, IsisOptions};
+/// let pvl = r#"
+/// Object = ControlNetwork
+///   Object = ControlPoint
+///     PointId = p0
+///     Group = ControlMeasure
+///       SerialNumber = CTX/1
+///       Sample = 2.0
+///       Line = 3.5
+///     End_Group
+///     Group = ControlMeasure
+///       SerialNumber = CTX/2
+///       Sample = 11.0
+///       Line = 12.0
+///     End_Group
+///   End_Object
+/// End_Object
+/// End
+/// "#;
+/// let track_set = parse_isis_pvl(pvl, &IsisOptions::default()).unwrap();
+/// assert_eq!(track_set.images, vec!["CTX/1", "CTX/2"]);
+/// let observation = &track_set.tracks[0].observations[0];
+/// assert_eq!((observation.x, observation.y), (1.0, 2.5));
+/// ```
+pub fn parse_isis_pvl(input: &str, options: &IsisOptions) -> Result<TrackSet, ASPMatchError> {
+    let offset = pixel_offset(options);
+    let mut track_set = TrackSet::default();
+    let mut image_indices: HashMap<&str, usize> = HashMap::new();
+    let mut blocks: Vec<Block> = vec![];
+    let mut points = 0;
+    let mut network = false;
+    let mut lines = input.split('\n');
+    while let Some(line) = lines.next() {
+        let statement = match line.find("/*") {
+            Some(comment) => &line[..comment],
+            None => line,
+        }
+        .trim();
+        if statement.is_empty() || statement.starts_with('#') {
+            continue;
+        }
+        let (keyword, value) = match statement.split_once('=') {
+            Some((keyword, value)) => (keyword.trim(), value.trim()),
+            None => (statement, ""),
+        };
+        if let Some(mut continuation) = Continuation::new(value) {
+            // only scalar keywords are used, skip the rest of the value
+            let closed = lines.by_ref().any(|line| continuation.closes(line));
+            if !closed {
+                let e = MatchParseError::new(value, ErrorKind::Eof)
+                    .with_expected_found("end of value", "end of input");
+                return Err(text_error(input, nom::Err::Failure(e)));
+            }
+            continue;
+        }
+        let is = |name: &str| keyword.eq_ignore_ascii_case(name);
+        if is("End") {
+            break;
+        } else if is("Object") {
+            let block = match (blocks.last(), unquote(value)) {
+                (None, name) if name.eq_ignore_ascii_case("ControlNetwork") => {
+                    network = true;
+                    Block::Network
+                }
+                (Some(Block::Network), name) if name.eq_ignore_ascii_case("ControlPoint") => {
+                    points += 1;
+                    Block::Point {
+                        index: points - 1,
+                        ignore: false,
+                        observations: vec![],
+                    }
+                }
+                _ => Block::Object,
+            };
+            blocks.push(block);
+        } else if is("Group") {
+            let block = match (blocks.last(), unquote(value)) {
+                (Some(Block::Point { .. }), name)
+                    if name.eq_ignore_ascii_case("ControlMeasure") =>
+                {
+                    Block::Measure {
+                        serial_number: None,
+                        sample: None,
+                        line: None,
+                        ignore: false,
+                    }
+                }
+                _ => Block::Group,
+            };
+            blocks.push(block);
+        } else if is("End_Object") || is("EndObject") || is("End_Group") || is("EndGroup") {
+            let group = keyword.to_ascii_lowercase().contains("group");
+            let block = match blocks.pop() {
+                Some(block) if block.is_group() == group => block,
+                block => {
+                    let expected = match block {
+                        Some(block) if block.is_group() => "End_Group",
+                        Some(_) => "End_Object",
+                        None => "End",
+                    };
+                    let e = MatchParseError::new(keyword, ErrorKind::Tag)
+                        .with_expected_found(expected, keyword);
+                    return Err(text_error(input, nom::Err::Failure(e)));
+                }
+            };
+            match (block, blocks.last_mut()) {
+                (
+                    Block::Point {
+                        ignore: false,
+                        mut observations,
+                        ..
+                    },
+                    _,
+                ) if !observations.is_empty() => {
+                    observations.sort_by(|a, b| {
+                        a.image
+                            .cmp(&b.image)
+                            .then(a.x.total_cmp(&b.x))
+                            .then(a.y.total_cmp(&b.y))
+                    });
+                    track_set.tracks.push(Track { observations });
+                }
+                (
+                    Block::Measure {
+                        serial_number,
+                        sample,
+                        line,
+                        ignore,
+                    },
+                    Some(Block::Point {
+                        index,
+                        observations,
+                        ..
+                    }),
+                ) => {
+                    let missing = match (serial_number, sample, line) {
+                        (None, _, _) => Some("SerialNumber"),
+                        (_, None, _) => Some("Sample"),
+                        (_, _, None) => Some("Line"),
+                        _ => None,
+                    };
+                    if let Some(field) = missing {
+                        let e = MatchParseError::new(keyword, ErrorKind::Tag)
+                            .with_index(*index)
+                            .with_field(field)
+                            .with_expected_found("keyword", keyword);
+                        return Err(text_error(input, nom::Err::Failure(e)));
+                    }
+                    if let (Some(serial_number), Some(sample), Some(line), false) =
+                        (serial_number, sample, line, ignore)
+                    {
+                        let image = *image_indices.entry(serial_number).or_insert_with(|| {
+                            track_set.images.push(serial_number.to_string());
+                            track_set.images.len() - 1
+                        });
+                        observations.push(Observation {
+                            image,
+                            x: (sample - offset) as f32,
+                            y: (line - offset) as f32,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        } else {
+            match blocks.last_mut() {
+                Some(Block::Point { ignore, .. }) if is("Ignore") => *ignore = is_true(value),
+                Some(Block::Measure {
+                    serial_number,
+                    sample,
+                    line,
+                    ignore,
+                }) => {
+                    let index = points - 1;
+                    let number = |field: &'static str| {
+                        // drop units, e.g. `<pixels>`
+                        let number = unquote(value.split('<').next().unwrap_or(value).trim());
+                        all_consuming(double)(number)
+                            .map(|(_, number)| number)
+                            .map_err(|e: nom::Err<MatchParseError<&str>>| {
+                                let e = e.map(|e| e.with_index(index).with_field(field));
+                                text_error(input, e)
+                            })
+                    };
+                    if is("SerialNumber") {
+                        *serial_number = Some(unquote(value));
+                    } else if is("Sample") {
+                        *sample = Some(number("Sample")?);
+                    } else if is("Line") {
+                        *line = Some(number("Line")?);
+                    } else if is("Ignore") {
+                        *ignore = is_true(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let expected = match blocks.last() {
+        Some(block) if block.is_group() => Some("End_Group"),
+        Some(_) => Some("End_Object"),
+        None if !network => Some("Object = ControlNetwork"),
+        None => None,
+    };
+    if let Some(expected) = expected {
+        let e = MatchParseError::new(&input[input.len()..], ErrorKind::Eof)
+            .with_expected_found(expected, "end of input");
+        return Err(text_error(input, nom::Err::Failure(e)));
+    }
+    Ok(track_set)
+}
+
+/// Parse a track set from an ISIS PVL control network reader, see [`parse_isis_pvl`]
+pub fn parse_isis_pvl_from_reader<R: Read>(
+    reader: R,
+    options: &IsisOptions,
+) -> Result<TrackSet, ASPMatchError> {
+    let mut text = String::new();
+    BufReader::new(reader).read_to_string(&mut text)?;
+    parse_isis_pvl(&text, options)
+}
+
+/// Parse a track set from an ISIS PVL control network file at path, see [`parse_isis_pvl`]
+pub fn parse_isis_pvl_file_path<P: AsRef<Path>>(
+    path: P,
+    options: &IsisOptions,
+) -> Result<TrackSet, ASPMatchError> {
+    parse_isis_pvl_from_reader(File::open(path)?, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord};
+    use crate::error::ASPMatchError;
+    use crate::isis::{dump_match_as_isis_pvl, dump_tracks_as_isis_pvl, parse_isis_pvl, timestamp};
+    use crate::options::IsisOptions;
+    use crate::tracks::{Observation, Track, TrackSet};
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn observation(image: usize, x: f32, y: f32) -> Observation {
+        Observation { image, x, y }
+    }
+
+    fn dummy_track_set() -> TrackSet {
+        TrackSet {
+            images: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            tracks: vec![
+                Track {
+                    observations: vec![observation(0, 0.1, 2.5), observation(1, 10.0, 20.0)],
+                },
+                // conflicting, skipped
+                Track {
+                    observations: vec![observation(0, 1.0, 1.0), observation(0, 2.0, 2.0)],
+                },
+                Track {
+                    observations: vec![
+                        observation(0, 5.0, 6.0),
+                        observation(1, 7.0, 8.0),
+                        observation(2, 1e-3, 1e6),
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn serial_numbers() -> HashMap<String, String> {
+        [("a", "CTX/A:1"), ("b", "CTX/B 2"), ("c", "CTX/C")]
+            .iter()
+            .map(|(image, serial)| (image.to_string(), serial.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tracks_round_trip() {
+        let track_set = dummy_track_set();
+        for one_based in [true, false] {
+            let options = IsisOptions {
+                one_based,
+                ..IsisOptions::default()
+            };
+            let mut pvl = vec![];
+            dump_tracks_as_isis_pvl(&track_set, &serial_numbers(), &mut pvl, &options).unwrap();
+            let pvl = String::from_utf8(pvl).unwrap();
+            assert!(pvl.contains("SerialNumber = \"CTX/B 2\""));
+            assert!(pvl.contains("PointId   = point_1"));
+            let parsed = parse_isis_pvl(&pvl, &options).unwrap();
+            assert_eq!(parsed.images, vec!["CTX/A:1", "CTX/B 2", "CTX/C"]);
+            let expected = vec![track_set.tracks[0].clone(), track_set.tracks[2].clone()];
+            assert_eq!(parsed.tracks, expected);
+        }
+        let mut serial_numbers = serial_numbers();
+        serial_numbers.remove("c");
+        let e = dump_tracks_as_isis_pvl(
+            &track_set,
+            &serial_numbers,
+            &mut vec![],
+            &IsisOptions::default(),
+        );
+        assert!(matches!(e, Err(ASPMatchError::MissingSerialNumber { image }) if image == "c"));
+    }
+
+    #[test]
+    fn test_double_quote_rejected() {
+        let mut serial_numbers = serial_numbers();
+        serial_numbers.insert("b".to_string(), "CTX/\"B\"".to_string());
+        let e = dump_tracks_as_isis_pvl(
+            &dummy_track_set(),
+            &serial_numbers,
+            &mut vec![],
+            &IsisOptions::default(),
+        );
+        match e {
+            Err(ASPMatchError::IO(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+                assert!(e.to_string().contains("CTX/\"B\""));
+            }
+            _ => panic!("unexpected {:?}", e),
+        }
+        let options = IsisOptions {
+            description: "\"quoted\"".to_string(),
+            ..IsisOptions::default()
+        };
+        let e = dump_match_as_isis_pvl(&IPMatch::default(), "s1", "s2", &mut vec![], &options);
+        assert!(matches!(e, Err(ASPMatchError::IO(_))));
+    }
+
+    #[test]
+    fn test_match_export() {
+        let record = |x: f32, y: f32| IPRecord {
+            x,
+            y,
+            ..IPRecord::default()
+        };
+        let ipmatch = IPMatch::from_pairs(vec![
+            (record(0.0, 0.0), record(1.0, 1.0)),
+            (record(2.0, 3.0), record(4.0, 5.0)),
+        ]);
+        let mut pvl = vec![];
+        dump_match_as_isis_pvl(&ipmatch, "s1", "s2", &mut pvl, &IsisOptions::default()).unwrap();
+        let parsed =
+            parse_isis_pvl(std::str::from_utf8(&pvl).unwrap(), &IsisOptions::default()).unwrap();
+        let matches = parsed.pairwise_matches();
+        assert_eq!(matches.len(), 1);
+        let parsed = &matches[&(0, 1)];
+        assert_eq!(parsed.image_1[1].x, 2.0);
+        assert_eq!(parsed.image_2[1].yi, 5);
+    }
+
+    #[test]
+    fn test_parse_isis_pvl() {
+        let pvl = "\
+/* exported by hand */
+Object = ControlNetwork
+  NetworkId = test
+  Version = 5
+  Object = ControlPoint
+    PointId = p0
+    Ignore = True
+    Group = ControlMeasure
+      SerialNumber = s1
+      Sample = 1.0
+      Line = 1.0
+    End_Group
+  End_Object
+  Object = ControlPoint
+    PointId = p1
+    AprioriCovarianceMatrix = (1.0, 0.0,
+                               0.0, 1.0)
+    Description = \"checked by hand
+      (twice)
+      End_Object
+      is part of the description\"
+    Notes = (\"a)\",
+             {b, c}, \"d\")
+    Group = ControlMeasure
+      SerialNumber = \"s1\"
+      Sample = 3.0 <pixels>
+      Line = 4.0
+      Ignore = False
+    End_Group
+    Group = ControlMeasure
+      SerialNumber = s2
+      Sample = 3.0
+      Line = 4.0
+      Ignore = True
+    End_Group
+  End_Object
+End_Object
+End
+";
+        let track_set = parse_isis_pvl(pvl, &IsisOptions::default()).unwrap();
+        assert_eq!(track_set.images, vec!["s1"]);
+        assert_eq!(track_set.tracks.len(), 1);
+        assert_eq!(
+            track_set.tracks[0].observations,
+            vec![observation(0, 2.0, 3.0)]
+        );
+    }
+
+    #[test]
+    fn test_parse_isis_pvl_errors() {
+        let options = IsisOptions::default();
+        let point = |measure: &str| {
+            format!(
+                "Object = ControlNetwork\n  Object = ControlPoint\n    Group = ControlMeasure\n{}    End_Group\n  End_Object\nEnd_Object\n",
+                measure
+            )
+        };
+        let e = parse_isis_pvl(
+            &point("      SerialNumber = s\n      Sample = x\n      Line = 1\n"),
+            &options,
+        );
+        match e {
+            Err(ASPMatchError::TextParser {
+                line,
+                column,
+                details,
+            }) => {
+                assert_eq!((line, column), (5, 16));
+                assert_eq!(details.to_string(), "record 0, field `Sample`, Float");
+            }
+            _ => panic!("unexpected {:?}", e),
+        }
+        let e = parse_isis_pvl(
+            &point("      SerialNumber = s\n      Sample = 1\n"),
+            &options,
+        );
+        match e {
+            Err(ASPMatchError::TextParser { line, details, .. }) => {
+                assert_eq!(line, 6);
+                assert_eq!(
+                    details.to_string(),
+                    "record 0, field `Line`, expected keyword, found End_Group"
+                );
+            }
+            _ => panic!("unexpected {:?}", e),
+        }
+        let e = parse_isis_pvl(
+            "Object = ControlNetwork\n  Object = ControlPoint\nEnd_Group\n",
+            &options,
+        );
+        assert!(
+            matches!(e, Err(ASPMatchError::TextParser { line: 3, ref details, .. }) if details.to_string() == "expected End_Object, found End_Group"),
+            "{:?}",
+            e
+        );
+        let e = parse_isis_pvl("Object = ControlNetwork\n", &options);
+        assert!(matches!(
+            e,
+            Err(ASPMatchError::TextParser {
+                line: 2,
+                column: 1,
+                ..
+            })
+        ));
+        assert!(parse_isis_pvl("", &options).is_err());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_secs(951782400 + 3600 + 120 + 3);
+        assert_eq!(timestamp(time), "2000-02-29T01:02:03");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00");
+    }
+}
@@ -65,14 +65,99 @@
 //! # Ok(())
 //! # }
 //! ```
+mod csv;
 mod data;
+mod error;
+mod format;
+mod geojson;
+mod ipfile;
+mod isis;
+mod matcher;
+mod naming;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
 mod parser;
+mod reader;
+mod salvage;
+mod tracks;
+mod validate;
+mod view;
+mod writer;
 
-pub use data::{IPMatch, IPRecord};
+pub use csv::{
+    dump_match_as_csv, dump_match_as_csv_to_path, dump_match_as_csv_to_path_with_options,
+    parse_csv_match, parse_csv_match_file_path, parse_csv_match_from_reader,
+};
+
+pub use data::{IPFile, IPMatch, IPRecord, MatchSide};
+
+pub use error::{ASPMatchError, MatchParseError, ParseErrorDetails, ParseResult};
+
+pub use format::{
+    detect_byte_order, detect_match_format, parse_match_auto_from_reader,
+    parse_match_auto_from_reader_with_options, parse_match_file_path_auto,
+    parse_match_file_path_auto_with_options, MatchFormat,
+};
+
+pub use geojson::{
+    dump_match_as_geojson, dump_match_as_geojson_to_path,
+    dump_match_as_geojson_to_path_with_options,
+};
+
+pub use ipfile::{
+    detect_ip_file_format, dump_ip_file_as_binary, dump_ip_file_as_binary_to_path,
+    dump_ip_file_as_binary_to_path_with_options, dump_ip_file_as_binary_with_byte_order,
+    dump_ip_file_as_text, dump_ip_file_as_text_to_path, dump_ip_file_as_text_to_path_with_options,
+    dump_ip_file_as_text_with_options, ipfile, ipfile_text, ipfile_text_with_options,
+    ipfile_with_options, parse_binary_ip_file_from_reader,
+    parse_binary_ip_file_from_reader_with_options, parse_binary_ip_file_path,
+    parse_binary_ip_file_path_with_options, parse_ip_file_auto_from_reader,
+    parse_ip_file_auto_from_reader_with_options, parse_ip_file_path_auto,
+    parse_ip_file_path_auto_with_options, parse_text_ip_file_from_reader,
+    parse_text_ip_file_from_reader_with_options, parse_text_ip_file_path,
+    parse_text_ip_file_path_with_options,
+};
+
+pub use isis::{
+    dump_match_as_isis_pvl, dump_match_as_isis_pvl_to_path,
+    dump_match_as_isis_pvl_to_path_with_options, dump_tracks_as_isis_pvl,
+    dump_tracks_as_isis_pvl_to_path, dump_tracks_as_isis_pvl_to_path_with_options, parse_isis_pvl,
+    parse_isis_pvl_file_path, parse_isis_pvl_from_reader,
+};
+
+pub use matcher::{match_descriptors, match_records};
+
+pub use naming::{
+    clean_match_file_path, match_file_path, parse_match_file_path, scan_match_files,
+    scan_match_files_with_prefix, MatchFileEntry,
+};
+
+pub use options::{
+    ByteOrder, CsvColumn, CsvReadOptions, CsvWriteOptions, DumpOptions, FloatFormat,
+    GeoJsonGeometry, GeoJsonOptions, IsisOptions, MatchAlgorithm, MatcherOptions, ParseLimits,
+    ParseOptions, SalvageOptions, TextParseMode, TextWriteOptions, TrackOptions, ValidationOptions,
+};
+
+#[cfg(feature = "rayon")]
+pub use parallel::ipmatch_text_parallel;
 
 pub use parser::{
     dump_match_as_binary, dump_match_as_binary_to_file, dump_match_as_binary_to_path,
-    dump_match_as_text, dump_match_as_text_to_file, dump_match_as_text_to_path, ipmatch,
-    ipmatch_text, iprecord, iprecord_text, parse_binary_match_file, parse_binary_match_file_path,
-    parse_text_match_file, parse_text_match_file_path, ASPMatchError,
+    dump_match_as_binary_to_path_with_options, dump_match_as_binary_with_byte_order,
+    dump_match_as_text, dump_match_as_text_to_file, dump_match_as_text_to_path,
+    dump_match_as_text_to_path_with_options, dump_match_as_text_with_options, ipmatch,
+    ipmatch_text, ipmatch_text_with_options, ipmatch_with_options, iprecord, iprecord_text,
+    iprecord_text_with_options, iprecord_with_options, parse_binary_match_file,
+    parse_binary_match_file_path, parse_binary_match_file_path_with_options,
+    parse_binary_match_from_reader, parse_binary_match_from_reader_with_options,
+    parse_text_match_file, parse_text_match_file_path, parse_text_match_file_path_with_options,
+    parse_text_match_from_reader, parse_text_match_from_reader_with_options,
 };
+
+pub use reader::MatchReader;
+pub use salvage::{salvage_binary_match, salvage_binary_match_file_path, SalvageReport};
+pub use tracks::{build_tracks_from_directory, Observation, Track, TrackBuilder, TrackSet};
+pub use validate::{IssueKind, Severity, ValidationIssue};
+pub use view::{DescView, MatchFileView, RecordView};
+pub use writer::MatchWriter;
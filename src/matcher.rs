@@ -0,0 +1,478 @@
+use crate::data::{IPMatch, IPRecord, MatchSide};
+use crate::error::ASPMatchError;
+use crate::options::{MatchAlgorithm, MatcherOptions};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Descriptors per kd-tree leaf
+const LEAF_SIZE: usize = 8;
+
+/// Nearest and second nearest `(squared distance, index)`
+type Neighbors = [Option<(f32, usize)>; 2];
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn insert_neighbor(neighbors: &mut Neighbors, distance: f32, index: usize) {
+    match *neighbors {
+        [Some((nearest, _)), _] if distance >= nearest => {
+            let closer = match neighbors[1] {
+                Some((second, _)) => distance < second,
+                None => true,
+            };
+            if closer {
+                neighbors[1] = Some((distance, index));
+            }
+        }
+        // NaN distances never become neighbors
+        _ if distance.is_nan() => {}
+        _ => {
+            neighbors[1] = neighbors[0];
+            neighbors[0] = Some((distance, index));
+        }
+    }
+}
+
+fn brute_force(records: &[IPRecord], query: &[f32]) -> Neighbors {
+    let mut neighbors = [None, None];
+    for (index, record) in records.iter().enumerate() {
+        insert_neighbor(&mut neighbors, squared_distance(query, &record.desc), index);
+    }
+    neighbors
+}
+
+enum Node {
+    Leaf {
+        start: usize,
+        end: usize,
+    },
+    Split {
+        dim: usize,
+        value: f32,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// kd-tree over record descriptors, split on the dimension of highest variance
+struct KdTree<'a> {
+    records: &'a [IPRecord],
+    indices: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+/// kd-tree branch to visit, ordered by increasing distance lower bound
+struct Branch {
+    bound: f32,
+    node: usize,
+}
+
+impl PartialEq for Branch {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Branch {}
+
+impl PartialOrd for Branch {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Branch {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap, closest branch first
+        other.bound.total_cmp(&self.bound)
+    }
+}
+
+impl<'a> KdTree<'a> {
+    fn new(records: &'a [IPRecord]) -> Self {
+        let mut tree = KdTree {
+            records,
+            indices: (0..records.len()).collect(),
+            nodes: vec![],
+        };
+        tree.build(0, records.len());
+        tree
+    }
+
+    /// Build the subtree of `indices[start..end]`, returns its node index
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Node::Leaf { start, end });
+        if end - start <= LEAF_SIZE {
+            return node;
+        }
+        let (dim, variance) = self.highest_variance(start, end);
+        // identical descriptors cannot be split
+        if variance.is_nan() || variance <= 0.0 {
+            return node;
+        }
+        let mid = start + (end - start) / 2;
+        let records = self.records;
+        self.indices[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            records[*a].desc[dim].total_cmp(&records[*b].desc[dim])
+        });
+        let value = records[self.indices[mid]].desc[dim];
+        let left = self.build(start, mid);
+        let right = self.build(mid, end);
+        self.nodes[node] = Node::Split {
+            dim,
+            value,
+            left,
+            right,
+        };
+        node
+    }
+
+    fn highest_variance(&self, start: usize, end: usize) -> (usize, f32) {
+        let dims = self.records[self.indices[start]].desc.len();
+        let n = (end - start) as f64;
+        (0..dims)
+            .map(|dim| {
+                let values = self.indices[start..end]
+                    .iter()
+                    .map(|index| self.records[*index].desc[dim] as f64);
+                let mean = values.clone().sum::<f64>() / n;
+                let variance = values.map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+                (dim, variance as f32)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, 0.0))
+    }
+
+    /// Best bin first search, examining at most `max_checks` descriptors
+    fn nearest(&self, query: &[f32], max_checks: usize) -> Neighbors {
+        let mut neighbors = [None, None];
+        if self.records.is_empty() {
+            return neighbors;
+        }
+        let mut checks = 0;
+        let mut branches = BinaryHeap::from([Branch {
+            bound: 0.0,
+            node: 0,
+        }]);
+        while let Some(Branch { bound, mut node }) = branches.pop() {
+            if neighbors[1].is_some_and(|(second, _)| bound >= second) {
+                break;
+            }
+            loop {
+                match self.nodes[node] {
+                    Node::Split {
+                        dim,
+                        value,
+                        left,
+                        right,
+                    } => {
+                        let diff = query[dim] - value;
+                        let (near, far) = if diff < 0.0 {
+                            (left, right)
+                        } else {
+                            (right, left)
+                        };
+                        branches.push(Branch {
+                            bound: bound.max(diff * diff),
+                            node: far,
+                        });
+                        node = near;
+                    }
+                    Node::Leaf { start, end } => {
+                        for index in &self.indices[start..end] {
+                            let distance = squared_distance(query, &self.records[*index].desc);
+                            insert_neighbor(&mut neighbors, distance, *index);
+                        }
+                        checks += end - start;
+                        break;
+                    }
+                }
+            }
+            if checks >= max_checks {
+                break;
+            }
+        }
+        neighbors
+    }
+}
+
+/// Search structure over the records of one image
+enum Index<'a> {
+    BruteForce(&'a [IPRecord]),
+    KdTree(KdTree<'a>, usize),
+}
+
+impl<'a> Index<'a> {
+    fn new(records: &'a [IPRecord], algorithm: MatchAlgorithm) -> Self {
+        match algorithm {
+            MatchAlgorithm::BruteForce => Index::BruteForce(records),
+            MatchAlgorithm::KdTree { max_checks } => {
+                Index::KdTree(KdTree::new(records), max_checks)
+            }
+        }
+    }
+
+    fn nearest(&self, query: &[f32]) -> Neighbors {
+        match self {
+            Index::BruteForce(records) => brute_force(records, query),
+            Index::KdTree(tree, max_checks) => tree.nearest(query, *max_checks),
+        }
+    }
+}
+
+/// Run `query` for each index in `0..n`, in parallel with the `rayon` feature
+fn map_queries<T, F>(n: usize, query: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        (0..n).into_par_iter().map(query).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        (0..n).map(query).collect()
+    }
+}
+
+fn check_descriptor_lengths(
+    records_1: &[IPRecord],
+    records_2: &[IPRecord],
+) -> Result<(), ASPMatchError> {
+    let expected = match records_1.first().or_else(|| records_2.first()) {
+        Some(record) => record.desc.len(),
+        None => return Ok(()),
+    };
+    let sides = [
+        (MatchSide::Image1, records_1),
+        (MatchSide::Image2, records_2),
+    ];
+    for (side, records) in sides {
+        if let Some((record, found)) = records
+            .iter()
+            .map(|record| record.desc.len())
+            .enumerate()
+            .find(|(_, found)| *found != expected)
+        {
+            return Err(ASPMatchError::DescriptorLength {
+                side,
+                record,
+                expected,
+                found,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Match descriptors of `records_1` against `records_2` by L2 distance,
+/// returns `(image 1 index, image 2 index)` pairs sorted by image 1 index
+///
+/// See [`match_records`].
+pub fn match_descriptors(
+    records_1: &[IPRecord],
+    records_2: &[IPRecord],
+    options: &MatcherOptions,
+) -> Result<Vec<(usize, usize)>, ASPMatchError> {
+    check_descriptor_lengths(records_1, records_2)?;
+    let index_2 = Index::new(records_2, options.algorithm);
+    let ratio_squared = options.ratio * options.ratio;
+    let nearest_2 = map_queries(records_1.len(), |i| {
+        match index_2.nearest(&records_1[i].desc) {
+            [Some((nearest, j)), Some((second, _))]
+                if options.ratio >= 1.0 || nearest < ratio_squared * second =>
+            {
+                Some(j)
+            }
+            [Some((_, j)), None] => Some(j),
+            _ => None,
+        }
+    });
+    let nearest_1 = if options.mutual {
+        let index_1 = Index::new(records_1, options.algorithm);
+        map_queries(records_2.len(), |j| {
+            index_1.nearest(&records_2[j].desc)[0].map(|(_, i)| i)
+        })
+    } else {
+        vec![]
+    };
+    Ok(nearest_2
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, j)| j.map(|j| (i, j)))
+        .filter(|(i, j)| !options.mutual || nearest_1[*j] == Some(*i))
+        .collect())
+}
+
+/// Match interest points of two images by descriptor, like ASP `ipmatch`
+///
+/// Each image 1 record is paired with the image 2 record whose descriptor is
+/// nearest (L2 distance) if it passes Lowe's ratio test and, optionally, the
+/// mutual nearest neighbor check. Descriptors must all have the same length.
+/// Queries run in parallel with the `rayon` feature.
+///
+/// ```
+/// use aspmatch::{match_records, IPRecord, MatcherOptions};
+/// let record = |x: f32, desc: Vec<f32>| IPRecord { x, desc, ..IPRecord::default() };
+/// let image_1 = vec![record(1.0, vec![0.0, 1.0]), record(2.0, vec![5.0, 5.0])];
+/// let image_2 = vec![record(3.0, vec![5.1, 4.9]), record(4.0, vec![0.1, 1.0])];
+/// let ipmatch = match_records(&image_1, &image_2, &MatcherOptions::default()).unwrap();
+/// assert_eq!(ipmatch.image_1, image_1);
+/// assert_eq!(ipmatch.image_2, vec![image_2[1].clone(), image_2[0].clone()]);
+/// ```
+pub fn match_records(
+    records_1: &[IPRecord],
+    records_2: &[IPRecord],
+    options: &MatcherOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    let pairs = match_descriptors(records_1, records_2, options)?;
+    Ok(pairs
+        .into_iter()
+        .map(|(i, j)| (records_1[i].clone(), records_2[j].clone()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPRecord, MatchSide};
+    use crate::error::ASPMatchError;
+    use crate::matcher::{match_descriptors, match_records};
+    use crate::options::{MatchAlgorithm, MatcherOptions};
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    fn random_records(rng: &mut StdRng, n: usize, dims: usize) -> Vec<IPRecord> {
+        (0..n)
+            .map(|i| IPRecord {
+                x: i as f32,
+                desc: (0..dims).map(|_| rng.gen()).collect(),
+                ..IPRecord::default()
+            })
+            .collect()
+    }
+
+    /// `records` shuffled with slightly perturbed descriptors, and the permutation
+    fn noisy_copy(rng: &mut StdRng, records: &[IPRecord]) -> (Vec<IPRecord>, Vec<usize>) {
+        let mut permutation: Vec<usize> = (0..records.len()).collect();
+        permutation.shuffle(rng);
+        let copy = permutation
+            .iter()
+            .map(|i| IPRecord {
+                desc: records[*i]
+                    .desc
+                    .iter()
+                    .map(|v| v + rng.gen_range(-0.001..0.001))
+                    .collect(),
+                ..records[*i].clone()
+            })
+            .collect();
+        (copy, permutation)
+    }
+
+    #[test]
+    fn test_kd_tree_exact_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let records_1 = random_records(&mut rng, 300, 16);
+        let records_2 = random_records(&mut rng, 500, 16);
+        for mutual in [false, true] {
+            let brute_force = MatcherOptions {
+                ratio: 0.9,
+                mutual,
+                ..MatcherOptions::default()
+            };
+            let kd_tree = MatcherOptions {
+                algorithm: MatchAlgorithm::KdTree {
+                    max_checks: usize::MAX,
+                },
+                ..brute_force.clone()
+            };
+            let expected = match_descriptors(&records_1, &records_2, &brute_force).unwrap();
+            assert!(!expected.is_empty());
+            assert_eq!(
+                match_descriptors(&records_1, &records_2, &kd_tree).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_recovers_noisy_copy() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let records_1 = random_records(&mut rng, 1000, 32);
+        let (records_2, permutation) = noisy_copy(&mut rng, &records_1);
+        let algorithms = [
+            MatchAlgorithm::BruteForce,
+            MatchAlgorithm::KdTree { max_checks: 256 },
+        ];
+        for algorithm in algorithms {
+            let options = MatcherOptions {
+                algorithm,
+                mutual: true,
+                ..MatcherOptions::default()
+            };
+            let pairs = match_descriptors(&records_1, &records_2, &options).unwrap();
+            // every pair found is correct, the approximate search may miss a few
+            assert!(pairs.iter().all(|(i, j)| permutation[*j] == *i));
+            assert!(pairs.len() >= 900, "{:?}: {} pairs", algorithm, pairs.len());
+        }
+        let ipmatch = match_records(&records_1, &records_2, &MatcherOptions::default()).unwrap();
+        assert_eq!(ipmatch.pair_count().unwrap(), 1000);
+        assert!(ipmatch.pairs().unwrap().all(|(a, b)| a.x == b.x));
+    }
+
+    #[test]
+    fn test_ratio_and_mutual() {
+        let record = |desc: Vec<f32>| IPRecord {
+            desc,
+            ..IPRecord::default()
+        };
+        // image 1 record 0 is as close to both image 2 records
+        let records_1 = vec![record(vec![0.0]), record(vec![10.0]), record(vec![10.3])];
+        let records_2 = vec![record(vec![-1.0]), record(vec![1.0]), record(vec![10.1])];
+        let options = MatcherOptions::default();
+        let pairs = match_descriptors(&records_1, &records_2, &options).unwrap();
+        assert_eq!(pairs, vec![(1, 2), (2, 2)]);
+        let mutual = MatcherOptions {
+            mutual: true,
+            ..MatcherOptions::default()
+        };
+        // 10.1 is nearest to 10.0, not to 10.3
+        let pairs = match_descriptors(&records_1, &records_2, &mutual).unwrap();
+        assert_eq!(pairs, vec![(1, 2)]);
+        let no_ratio = MatcherOptions {
+            ratio: 1.0,
+            ..MatcherOptions::default()
+        };
+        let pairs = match_descriptors(&records_1, &records_2, &no_ratio).unwrap();
+        assert_eq!(pairs.len(), 3);
+        assert!(match_descriptors(&records_1, &[], &options)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_descriptor_length() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut records_2 = random_records(&mut rng, 3, 4);
+        records_2[2].desc.pop();
+        let e = match_descriptors(
+            &random_records(&mut rng, 2, 4),
+            &records_2,
+            &MatcherOptions::default(),
+        );
+        assert!(matches!(
+            e,
+            Err(ASPMatchError::DescriptorLength {
+                side: MatchSide::Image2,
+                record: 2,
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::ASPMatchError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MATCH_EXTENSION: &str = ".match";
+const CLEAN_SUFFIX: &str = "-clean";
+const IMAGE_SEPARATOR: &str = "__";
+
+/// Match file found by [`scan_match_files`] or parsed by [`parse_match_file_path`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchFileEntry {
+    /// image 1 stem (file name without directory and extension)
+    pub image_1: String,
+    /// image 2 stem
+    pub image_2: String,
+    pub path: PathBuf,
+    /// `-clean.match` file, written after outlier filtering
+    pub clean: bool,
+}
+
+impl MatchFileEntry {
+    /// Find the images whose stems are `image_1` and `image_2` among `images`
+    ///
+    /// Image stems containing `__` are resolved as well, whichever split of the
+    /// file name matches two images. If several images share a stem, the first
+    /// one wins.
+    ///
+    /// ```
+    /// use aspmatch::parse_match_file_path;
+    /// use std::path::PathBuf;
+    /// let entry = parse_match_file_path("out/run-left__right.match", Some("out/run")).unwrap();
+    /// let images = ["data/left.tif", "data/right.tif"];
+    /// let (image_1, image_2) = entry.resolve_images(&images).unwrap();
+    /// assert_eq!(image_1, PathBuf::from("data/left.tif"));
+    /// assert_eq!(image_2, PathBuf::from("data/right.tif"));
+    /// ```
+    pub fn resolve_images<P: AsRef<Path>>(&self, images: &[P]) -> Option<(PathBuf, PathBuf)> {
+        let pair = format!("{}{}{}", self.image_1, IMAGE_SEPARATOR, self.image_2);
+        images.iter().find_map(|image_1| {
+            let stem_2 = pair
+                .strip_prefix(&image_stem(image_1))?
+                .strip_prefix(IMAGE_SEPARATOR)?;
+            let image_2 = images
+                .iter()
+                .find(|image_2| image_stem(image_2) == stem_2)?;
+            Some((
+                image_1.as_ref().to_path_buf(),
+                image_2.as_ref().to_path_buf(),
+            ))
+        })
+    }
+}
+
+fn image_stem<P: AsRef<Path>>(image: P) -> String {
+    image
+        .as_ref()
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// File name part of an output prefix, `run` for `out/run`, empty for `out/`
+fn prefix_file_name(prefix: &str) -> &str {
+    prefix
+        .rsplit(['/', std::path::MAIN_SEPARATOR])
+        .next()
+        .unwrap_or(prefix)
+}
+
+fn match_file_path_with_suffix<P: AsRef<Path>, Q: AsRef<Path>>(
+    prefix: &str,
+    image_1: P,
+    image_2: Q,
+    suffix: &str,
+) -> PathBuf {
+    PathBuf::from(format!(
+        "{}-{}{}{}{}{}",
+        prefix,
+        image_stem(image_1),
+        IMAGE_SEPARATOR,
+        image_stem(image_2),
+        suffix,
+        MATCH_EXTENSION
+    ))
+}
+
+/// ASP match file path of an image pair: `<prefix>-<image1 stem>__<image2 stem>.match`
+///
+/// `prefix` is the ASP output prefix, e.g. `ba/run`, as expected by
+/// `bundle_adjust --match-files-prefix`.
+///
+/// ```
+/// use aspmatch::match_file_path;
+/// use std::path::PathBuf;
+/// let path = match_file_path("ba/run", "data/left.tif", "data/right.cub");
+/// assert_eq!(path, PathBuf::from("ba/run-left__right.match"));
+/// ```
+pub fn match_file_path<P: AsRef<Path>, Q: AsRef<Path>>(
+    prefix: &str,
+    image_1: P,
+    image_2: Q,
+) -> PathBuf {
+    match_file_path_with_suffix(prefix, image_1, image_2, "")
+}
+
+/// ASP filtered match file path of an image pair: `<prefix>-<image1 stem>__<image2 stem>-clean.match`
+pub fn clean_match_file_path<P: AsRef<Path>, Q: AsRef<Path>>(
+    prefix: &str,
+    image_1: P,
+    image_2: Q,
+) -> PathBuf {
+    match_file_path_with_suffix(prefix, image_1, image_2, CLEAN_SUFFIX)
+}
+
+/// Parse an ASP match file name, `None` if it does not follow the convention
+///
+/// With a `prefix` (ASP output prefix such as `ba/run`, only its file name
+/// part is compared) the file name must start with it. Without a prefix, the
+/// file name is split at its first `-`, pass the prefix if it contains `-`.
+/// Image stems are split at the first `__`, see [`MatchFileEntry::resolve_images`].
+///
+/// ```
+/// use aspmatch::parse_match_file_path;
+/// let entry = parse_match_file_path("ba/run-left__right-clean.match", None).unwrap();
+/// assert_eq!(entry.image_1, "left");
+/// assert_eq!(entry.image_2, "right");
+/// assert!(entry.clean);
+/// assert!(parse_match_file_path("ba/run-left__right.match", Some("ba/other")).is_none());
+/// ```
+pub fn parse_match_file_path<P: AsRef<Path>>(
+    path: P,
+    prefix: Option<&str>,
+) -> Option<MatchFileEntry> {
+    let path = path.as_ref();
+    let name = path.file_name()?.to_str()?.strip_suffix(MATCH_EXTENSION)?;
+    let (name, clean) = match name.strip_suffix(CLEAN_SUFFIX) {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let pair = match prefix {
+        Some(prefix) => name
+            .strip_prefix(prefix_file_name(prefix))?
+            .strip_prefix('-')?,
+        None => name.split_once('-')?.1,
+    };
+    let (image_1, image_2) = pair.split_once(IMAGE_SEPARATOR)?;
+    if image_1.is_empty() || image_2.is_empty() {
+        return None;
+    }
+    Some(MatchFileEntry {
+        image_1: image_1.to_string(),
+        image_2: image_2.to_string(),
+        path: path.to_path_buf(),
+        clean,
+    })
+}
+
+/// List the match files of `directory`, sorted by path
+///
+/// Files whose name does not follow the convention are ignored, see
+/// [`parse_match_file_path`] for `prefix`.
+pub fn scan_match_files<P: AsRef<Path>>(
+    directory: P,
+    prefix: Option<&str>,
+) -> Result<Vec<MatchFileEntry>, ASPMatchError> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(entry) = parse_match_file_path(path, prefix) {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// List the match files written with the ASP output `prefix`, e.g. `ba/run`
+///
+/// ```
+/// use aspmatch::{match_file_path, scan_match_files_with_prefix, IPMatch};
+/// # use aspmatch::{dump_match_as_binary_to_path, ASPMatchError};
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let directory = tempfile::tempdir()?;
+/// let prefix = directory.path().join("run");
+/// let prefix = prefix.to_str().unwrap();
+/// let path = match_file_path(prefix, "left.tif", "right.tif");
+/// dump_match_as_binary_to_path(&IPMatch::default(), &path)?;
+/// let entries = scan_match_files_with_prefix(prefix)?;
+/// assert_eq!(entries.len(), 1);
+/// assert_eq!(entries[0].path, path);
+/// # Ok(())
+/// # }
+/// ```
+pub fn scan_match_files_with_prefix(prefix: &str) -> Result<Vec<MatchFileEntry>, ASPMatchError> {
+    let directory = if prefix_file_name(prefix).is_empty() {
+        Path::new(prefix)
+    } else {
+        match Path::new(prefix).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    };
+    scan_match_files(directory, Some(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::naming::{
+        clean_match_file_path, match_file_path, parse_match_file_path, scan_match_files,
+        scan_match_files_with_prefix, MatchFileEntry,
+    };
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_round_trip() {
+        let path = match_file_path("out/my-run", "a/img-1.tif", "b/img_2.cub");
+        assert_eq!(path, PathBuf::from("out/my-run-img-1__img_2.match"));
+        let entry = parse_match_file_path(&path, Some("out/my-run")).unwrap();
+        assert_eq!(
+            entry,
+            MatchFileEntry {
+                image_1: "img-1".to_string(),
+                image_2: "img_2".to_string(),
+                path,
+                clean: false,
+            }
+        );
+        let path = clean_match_file_path("out/", "img1.tif", "img2.tif");
+        assert_eq!(path, PathBuf::from("out/-img1__img2-clean.match"));
+        let entry = parse_match_file_path(&path, Some("out/")).unwrap();
+        assert_eq!((entry.image_1.as_str(), entry.clean), ("img1", true));
+        // the prefix contains '-', the first one is taken without it
+        let entry = parse_match_file_path("my-run-img1__img2.match", None).unwrap();
+        assert_eq!(entry.image_1, "run-img1");
+    }
+
+    #[test]
+    fn test_not_match_files() {
+        let names = [
+            "run-img1__img2.txt",
+            "run-img1_img2.match",
+            "run-__img2.match",
+            "run-img1__.match",
+            "run.match",
+            "img1__img2.match",
+        ];
+        for name in names {
+            assert!(parse_match_file_path(name, None).is_none(), "{}", name);
+        }
+        assert!(parse_match_file_path("other-img1__img2.match", Some("run")).is_none());
+    }
+
+    #[test]
+    fn test_resolve_images() {
+        let entry = parse_match_file_path("run-a__b__c.match", Some("run")).unwrap();
+        assert_eq!(
+            (entry.image_1.as_str(), entry.image_2.as_str()),
+            ("a", "b__c")
+        );
+        let images = ["x/a__b.tif", "y/c.cub", "z/d.tif"];
+        let (image_1, image_2) = entry.resolve_images(&images).unwrap();
+        assert_eq!(image_1, PathBuf::from("x/a__b.tif"));
+        assert_eq!(image_2, PathBuf::from("y/c.cub"));
+        assert!(entry.resolve_images(&["a.tif", "c.tif"]).is_none());
+    }
+
+    #[test]
+    fn test_scan() {
+        let directory = tempfile::tempdir().unwrap();
+        let prefix = directory.path().join("run");
+        let prefix = prefix.to_str().unwrap();
+        let paths = [
+            match_file_path(prefix, "img2", "img3"),
+            match_file_path(prefix, "img1", "img2"),
+            clean_match_file_path(prefix, "img1", "img2"),
+            match_file_path(&format!("{}-other", prefix), "img1", "img2"),
+            directory.path().join("run-log.txt"),
+        ];
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+        }
+        fs::create_dir(directory.path().join("run-img4__img5.match")).unwrap();
+        let entries = scan_match_files_with_prefix(prefix).unwrap();
+        let found: Vec<(&str, &str, bool)> = entries
+            .iter()
+            .map(|e| (e.image_1.as_str(), e.image_2.as_str(), e.clean))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("img1", "img2", true),
+                ("img1", "img2", false),
+                ("img2", "img3", false),
+                // other prefix, images are "other-img1" and "img2"
+                ("other-img1", "img2", false),
+            ]
+        );
+        assert_eq!(scan_match_files(directory.path(), None).unwrap().len(), 4);
+        assert!(scan_match_files(directory.path().join("missing"), None).is_err());
+    }
+}
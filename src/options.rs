@@ -0,0 +1,415 @@
+use crate::data::MatchSide;
+use nom::number::Endianness;
+use std::path::PathBuf;
+
+/// Byte order of binary match files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// written by ASP on x86/ARM machines
+    #[default]
+    LittleEndian,
+    /// found in archives produced on big endian machines
+    BigEndian,
+}
+
+impl ByteOrder {
+    pub(crate) fn endianness(self) -> Endianness {
+        match self {
+            ByteOrder::LittleEndian => Endianness::Little,
+            ByteOrder::BigEndian => Endianness::Big,
+        }
+    }
+
+    /// u64 at `offset`, panics if `bytes` is too short
+    pub(crate) fn u64_at(self, bytes: &[u8], offset: usize) -> u64 {
+        let bytes = bytes_at(bytes, offset);
+        match self {
+            ByteOrder::LittleEndian => u64::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u64::from_be_bytes(bytes),
+        }
+    }
+
+    /// u32 at `offset`, panics if `bytes` is too short
+    pub(crate) fn u32_at(self, bytes: &[u8], offset: usize) -> u32 {
+        let bytes = bytes_at(bytes, offset);
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        }
+    }
+
+    pub(crate) fn i32_at(self, bytes: &[u8], offset: usize) -> i32 {
+        self.u32_at(bytes, offset) as i32
+    }
+
+    pub(crate) fn f32_at(self, bytes: &[u8], offset: usize) -> f32 {
+        f32::from_bits(self.u32_at(bytes, offset))
+    }
+}
+
+fn bytes_at<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    let mut array = [0u8; N];
+    array.copy_from_slice(&bytes[offset..offset + N]);
+    array
+}
+
+/// How strictly text match files are parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextParseMode {
+    /// layout written by `as_text`: spaces or tabs between fields, one line
+    /// ending between records, a single final line ending at most
+    #[default]
+    Strict,
+    /// also accept blank lines, `#` comments, leading and trailing whitespace
+    /// on any line, e.g. files edited by hand
+    Lenient,
+}
+
+/// Upper bounds enforced while parsing, for match files from untrusted sources
+///
+/// Defaults are unlimited. Whatever the limits, no record or descriptor is
+/// allocated before the bytes it is decoded from are known to exist.
+///
+/// ```
+/// use aspmatch::{ipmatch_with_options, IPMatch, IPRecord, ParseLimits, ParseOptions};
+/// let record = IPRecord { desc: vec![0.0; 128], ..IPRecord::default() };
+/// let ipmatch = IPMatch { image_1: vec![record.clone()], image_2: vec![record] };
+/// let options = ParseOptions {
+///     limits: ParseLimits { max_ndesc: 64, ..ParseLimits::default() },
+///     ..ParseOptions::default()
+/// };
+/// assert!(ipmatch_with_options(&ipmatch.as_le_bytes(), &options).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// maximum `ndesc` of a single record
+    pub max_ndesc: u64,
+    /// maximum number of records, `size_1 + size_2`
+    pub max_records: u64,
+    /// maximum size of the whole match content in bytes
+    pub max_total_bytes: u64,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_ndesc: u64::MAX,
+            max_records: u64::MAX,
+            max_total_bytes: u64::MAX,
+        }
+    }
+}
+
+/// Options accepted by the `_with_options` parse entry points
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseOptions {
+    /// byte order of binary match files, ignored for text
+    pub byte_order: ByteOrder,
+    /// text parsing mode, ignored for binary
+    pub text_mode: TextParseMode,
+    pub limits: ParseLimits,
+}
+
+/// How floats are formatted in text match files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// shortest decimal representation parsing back to the same f32, e.g. `0.1`
+    #[default]
+    Shortest,
+    /// hexadecimal significand and binary exponent, e.g. `0x1.99999ap-4`
+    Hex,
+    /// fixed number of decimals, lossy when too small, e.g. `0.100` for 3
+    Fixed(usize),
+}
+
+/// Options accepted by the `_with_options` text dump entry points
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextWriteOptions {
+    pub float_format: FloatFormat,
+}
+
+/// GeoJSON geometry of each exported pair
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GeoJsonGeometry {
+    /// segment from the image 1 point to the image 2 point
+    #[default]
+    LineString,
+    /// image 1 point and image 2 point
+    MultiPoint,
+}
+
+/// Options accepted by the GeoJSON dump entry points
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GeoJsonOptions {
+    pub geometry: GeoJsonGeometry,
+    /// write `-y` instead of `y`, QGIS displays rasters without
+    /// georeferencing with row `r` at `y = -r`
+    pub flip_y: bool,
+}
+
+/// Column of a CSV match, named after the record field and the image side
+/// (`x1`, `y1`, `x2`, `y2`, `interest1`, `ndesc2`...)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvColumn {
+    /// pair index, ignored on import
+    Pair,
+    X(MatchSide),
+    Y(MatchSide),
+    Xi(MatchSide),
+    Yi(MatchSide),
+    Orientation(MatchSide),
+    Scale(MatchSide),
+    Interest(MatchSide),
+    Polarity(MatchSide),
+    Octave(MatchSide),
+    ScaleLvl(MatchSide),
+    /// descriptor length, ignored on import
+    Ndesc(MatchSide),
+}
+
+const CSV_SIDE_COLUMNS: [fn(MatchSide) -> CsvColumn; 11] = [
+    CsvColumn::X,
+    CsvColumn::Y,
+    CsvColumn::Xi,
+    CsvColumn::Yi,
+    CsvColumn::Orientation,
+    CsvColumn::Scale,
+    CsvColumn::Interest,
+    CsvColumn::Polarity,
+    CsvColumn::Octave,
+    CsvColumn::ScaleLvl,
+    CsvColumn::Ndesc,
+];
+
+impl CsvColumn {
+    /// Header name, e.g. `scale_lvl1`
+    ///
+    /// ```
+    /// use aspmatch::{CsvColumn, MatchSide};
+    /// assert_eq!(CsvColumn::X(MatchSide::Image2).name(), "x2");
+    /// assert_eq!(CsvColumn::from_name("X2"), Some(CsvColumn::X(MatchSide::Image2)));
+    /// ```
+    pub fn name(self) -> &'static str {
+        let (side, names) = match self {
+            CsvColumn::Pair => return "pair",
+            CsvColumn::X(side) => (side, ["x1", "x2"]),
+            CsvColumn::Y(side) => (side, ["y1", "y2"]),
+            CsvColumn::Xi(side) => (side, ["xi1", "xi2"]),
+            CsvColumn::Yi(side) => (side, ["yi1", "yi2"]),
+            CsvColumn::Orientation(side) => (side, ["orientation1", "orientation2"]),
+            CsvColumn::Scale(side) => (side, ["scale1", "scale2"]),
+            CsvColumn::Interest(side) => (side, ["interest1", "interest2"]),
+            CsvColumn::Polarity(side) => (side, ["polarity1", "polarity2"]),
+            CsvColumn::Octave(side) => (side, ["octave1", "octave2"]),
+            CsvColumn::ScaleLvl(side) => (side, ["scale_lvl1", "scale_lvl2"]),
+            CsvColumn::Ndesc(side) => (side, ["ndesc1", "ndesc2"]),
+        };
+        match side {
+            MatchSide::Image1 => names[0],
+            MatchSide::Image2 => names[1],
+        }
+    }
+
+    /// Column from its header name, ignoring ASCII case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let sides = [MatchSide::Image1, MatchSide::Image2];
+        std::iter::once(CsvColumn::Pair)
+            .chain(
+                sides
+                    .into_iter()
+                    .flat_map(|side| CSV_SIDE_COLUMNS.iter().map(move |column| column(side))),
+            )
+            .find(|column| column.name().eq_ignore_ascii_case(name))
+    }
+
+    /// `x1`, `y1`, `x2`, `y2`, the columns most tools exchange tie points with
+    pub fn coordinates() -> Vec<CsvColumn> {
+        vec![
+            CsvColumn::X(MatchSide::Image1),
+            CsvColumn::Y(MatchSide::Image1),
+            CsvColumn::X(MatchSide::Image2),
+            CsvColumn::Y(MatchSide::Image2),
+        ]
+    }
+}
+
+/// Options accepted by the CSV dump entry points
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvWriteOptions {
+    /// written in this order, `x1`, `y1`, `x2`, `y2` by default
+    pub columns: Vec<CsvColumn>,
+    pub delimiter: char,
+    /// write column names on the first line
+    pub header: bool,
+    pub float_format: FloatFormat,
+}
+
+impl Default for CsvWriteOptions {
+    fn default() -> Self {
+        CsvWriteOptions {
+            columns: CsvColumn::coordinates(),
+            delimiter: ',',
+            header: true,
+            float_format: FloatFormat::default(),
+        }
+    }
+}
+
+/// Options accepted by the CSV parse entry points
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvReadOptions {
+    pub delimiter: char,
+    /// the first line names the columns, in any order, unknown names are ignored
+    pub header: bool,
+    /// columns of each line when there is no header, `x1`, `y1`, `x2`, `y2` by default
+    pub columns: Vec<CsvColumn>,
+    /// scale of records without a `scale1` or `scale2` column
+    pub scale: f32,
+}
+
+impl Default for CsvReadOptions {
+    fn default() -> Self {
+        CsvReadOptions {
+            delimiter: ',',
+            header: true,
+            columns: CsvColumn::coordinates(),
+            scale: 1.0,
+        }
+    }
+}
+
+/// Options accepted by the `dump_*_to_path_with_options` functions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DumpOptions {
+    /// replace an existing file at path, otherwise fail with `AlreadyExists`
+    pub overwrite: bool,
+    /// text formatting, ignored for binary
+    pub text: TextWriteOptions,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        DumpOptions {
+            overwrite: true,
+            text: TextWriteOptions::default(),
+        }
+    }
+}
+
+/// Options accepted by the salvage functions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SalvageOptions {
+    /// byte order of the damaged binary match file
+    pub byte_order: ByteOrder,
+    /// write the salvaged match to this path, may be the damaged file itself
+    pub rewrite: Option<PathBuf>,
+    pub limits: ParseLimits,
+}
+
+/// Options accepted by [`IPMatch::validate`](crate::IPMatch::validate)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationOptions {
+    /// report `xi`/`yi` differing from `x`/`y` by `integer_tolerance` or more
+    pub check_integer_coordinates: bool,
+    /// accepts both rounded and truncated coordinates when set to 1
+    pub integer_tolerance: f32,
+    /// report negative `x`/`y`
+    pub check_negative_coordinates: bool,
+    /// report integer and negative coordinates as errors instead of warnings
+    pub strict: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            check_integer_coordinates: true,
+            integer_tolerance: 1.0,
+            check_negative_coordinates: true,
+            strict: false,
+        }
+    }
+}
+
+/// Nearest neighbor search used by the descriptor matcher
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchAlgorithm {
+    /// exact, compares every pair of descriptors
+    #[default]
+    BruteForce,
+    /// best bin first kd-tree search examining at most `max_checks`
+    /// descriptors per query, exact with `usize::MAX`
+    KdTree { max_checks: usize },
+}
+
+/// Options accepted by [`match_records`](crate::match_records)
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatcherOptions {
+    /// Lowe's ratio test: keep a match if the nearest descriptor is closer
+    /// than `ratio` times the second nearest, `1.0` keeps any nearest neighbor
+    pub ratio: f32,
+    /// only keep matches whose image 2 descriptor has the image 1 descriptor
+    /// as nearest neighbor too
+    pub mutual: bool,
+    pub algorithm: MatchAlgorithm,
+}
+
+impl Default for MatcherOptions {
+    fn default() -> Self {
+        MatcherOptions {
+            ratio: 0.8,
+            mutual: false,
+            algorithm: MatchAlgorithm::default(),
+        }
+    }
+}
+
+/// Options accepted by [`TrackBuilder`](crate::TrackBuilder)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackOptions {
+    /// interest points of the same image closer than `tolerance` pixels are
+    /// the same point, `0.0` requires identical coordinates
+    pub tolerance: f32,
+    /// when scanning a directory, use `-clean.match` files instead of the
+    /// unfiltered ones of the same image pair
+    pub prefer_clean: bool,
+    /// parse options of the match files read from a directory
+    pub parse: ParseOptions,
+}
+
+impl Default for TrackOptions {
+    fn default() -> Self {
+        TrackOptions {
+            tolerance: 0.5,
+            prefer_clean: true,
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+/// Options of the ISIS control network (PVL) export and import
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IsisOptions {
+    pub network_id: String,
+    /// target body, e.g. `Mars`, required by `jigsaw`
+    pub target_name: String,
+    pub user_name: String,
+    pub description: String,
+    /// control point ids are `point_id_prefix` followed by the point index
+    pub point_id_prefix: String,
+    /// ISIS `Sample`/`Line` are 1-based (the first pixel center is `1.0`),
+    /// ASP `x`/`y` are 0-based: add 1 on export and subtract 1 on import
+    pub one_based: bool,
+}
+
+impl Default for IsisOptions {
+    fn default() -> Self {
+        IsisOptions {
+            network_id: "aspmatch".to_string(),
+            target_name: String::new(),
+            user_name: "aspmatch".to_string(),
+            description: "ASP tie points".to_string(),
+            point_id_prefix: "point_".to_string(),
+            one_based: true,
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::data::{IPMatch, IPRecord};
+use crate::error::{MatchParseError, ParseResult};
+use crate::options::ParseOptions;
+use crate::parser::{
+    ipmatch_text_header, iprecord_text, iprecord_text_with_options, record_location,
+};
+use nom::character::complete::line_ending;
+use nom::error::ErrorKind;
+use nom::sequence::preceded;
+use nom::Offset;
+use rayon::prelude::*;
+
+/// Number of record lines parsed by a single rayon task
+const LINES_PER_CHUNK: usize = 4096;
+
+/// Parse IPMatch from text, parsing record lines in parallel
+///
+/// Same input, output and errors as [`crate::ipmatch_text`]: the body is split
+/// on line boundaries after the `size_1 size_2` header, chunks of lines are
+/// parsed with [`crate::iprecord_text`] on the rayon thread pool and
+/// reassembled in order.
+///
+/// ```
+/// use aspmatch::{ipmatch_text, ipmatch_text_parallel, IPMatch, IPRecord};
+/// let record = IPRecord { desc: vec![1.0, 2.0], ..IPRecord::default() };
+/// let ipmatch = IPMatch { image_1: vec![record.clone()], image_2: vec![record] };
+/// let text = ipmatch.as_text();
+/// let (_, parsed) = ipmatch_text_parallel(&text).unwrap();
+/// assert_eq!(parsed, ipmatch_text(&text).unwrap().1);
+/// ```
+pub fn ipmatch_text_parallel(input: &str) -> ParseResult<&str, IPMatch> {
+    ipmatch_text_parallel_with_options(input, &ParseOptions::default())
+}
+
+/// Parse IPMatch from text in parallel within `options.limits`, strict mode only
+pub(crate) fn ipmatch_text_parallel_with_options<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPMatch> {
+    let (body, (size_1, records)) = ipmatch_text_header(input, &options.limits)?;
+    let mut lines = vec![];
+    let mut rest = body;
+    for index in 0..records {
+        if index > 0 {
+            if rest.is_empty() {
+                return Err(nom::Err::Error(
+                    MatchParseError::new(rest, ErrorKind::Eof).with_expected_found(
+                        format!("{} records", records),
+                        format!("{} records", index),
+                    ),
+                ));
+            }
+            rest = line_ending(rest)
+                .map_err(|e: nom::Err<MatchParseError<&str>>| {
+                    let (side, record) = record_location(size_1, index);
+                    e.map(|e| e.with_record(side, record))
+                })?
+                .0;
+        }
+        let end = rest.find('\n').unwrap_or(rest.len());
+        let line = &rest[..end];
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        lines.push(line);
+        rest = &rest[line.len()..];
+    }
+
+    let last = lines.len().saturating_sub(1);
+    let chunks: Vec<Result<Vec<IPRecord>, nom::Err<MatchParseError<&str>>>> = lines
+        .par_chunks(LINES_PER_CHUNK)
+        .enumerate()
+        .map(|(chunk, lines)| {
+            let first = chunk * LINES_PER_CHUNK;
+            let mut ip_records = Vec::with_capacity(lines.len());
+            for (offset, line) in lines.iter().enumerate() {
+                let index = first + offset;
+                let (side, record) = record_location(size_1, index as u64);
+                let (remaining, ip_record) = iprecord_text_with_options(line, options)
+                    .map_err(|e| e.map(|e| e.with_record(side, record)))?;
+                if index != last && !remaining.is_empty() {
+                    // the sequential parser fails on the next line ending
+                    let (side, record) = record_location(size_1, index as u64 + 1);
+                    return Err(nom::Err::Error(
+                        MatchParseError::new(remaining, ErrorKind::CrLf).with_record(side, record),
+                    ));
+                }
+                ip_records.push(ip_record);
+            }
+            Ok(ip_records)
+        })
+        .collect();
+
+    let mut ip_records = Vec::with_capacity(lines.len());
+    for chunk in chunks {
+        ip_records.extend(chunk?);
+    }
+
+    // remaining input starts after what the last record consumed
+    let i = match lines.last() {
+        Some(line) => {
+            let (remaining, _) = iprecord_text(line)?;
+            &body[body.offset(remaining)..]
+        }
+        None => body,
+    };
+    if preceded(line_ending, iprecord_text)(i).is_ok() {
+        return Err(nom::Err::Error(
+            MatchParseError::new(i, ErrorKind::Verify)
+                .with_expected_found(format!("{} records", records), "more records"),
+        ));
+    }
+    let image_2_ip_records = ip_records.split_off(size_1 as usize);
+    Ok((
+        i,
+        IPMatch {
+            image_1: ip_records,
+            image_2: image_2_ip_records,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::error::{text_error, ASPMatchError};
+    use crate::parallel::{ipmatch_text_parallel, LINES_PER_CHUNK};
+    use crate::parser::ipmatch_text;
+    use rand::Rng;
+
+    fn random_match(size_1: usize, size_2: usize) -> IPMatch {
+        let mut rng = rand::thread_rng();
+        let mut record = || IPRecord {
+            x: rng.gen(),
+            y: rng.gen(),
+            xi: rng.gen(),
+            yi: rng.gen(),
+            desc: (0..rng.gen_range(0..4)).map(|_| rng.gen()).collect(),
+            ..IPRecord::default()
+        };
+        IPMatch {
+            image_1: (0..size_1).map(|_| record()).collect(),
+            image_2: (0..size_2).map(|_| record()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let ipmatch = random_match(LINES_PER_CHUNK + 10, LINES_PER_CHUNK * 2);
+        let text = ipmatch.as_text();
+        let parallel = ipmatch_text_parallel(&text);
+        assert_eq!(parallel, ipmatch_text(&text));
+        assert_eq!(parallel.unwrap().1, ipmatch);
+    }
+
+    #[test]
+    fn test_parallel_default_and_crlf() {
+        let text = IPMatch::default().as_text();
+        assert_eq!(ipmatch_text_parallel(&text), ipmatch_text(&text));
+        let text = random_match(3, 3).as_text().replace('\n', "\r\n");
+        assert_eq!(ipmatch_text_parallel(&text), ipmatch_text(&text));
+    }
+
+    #[test]
+    fn test_parallel_error_location() {
+        let mut lines: Vec<String> = random_match(LINES_PER_CHUNK, 2)
+            .as_text()
+            .lines()
+            .map(String::from)
+            .collect();
+        lines[LINES_PER_CHUNK + 2].insert(0, 'x');
+        let text = lines.join("\n");
+        let e = text_error(&text, ipmatch_text_parallel(&text).unwrap_err());
+        match e {
+            ASPMatchError::TextParser {
+                line,
+                column,
+                details,
+            } => {
+                assert_eq!((line, column), (LINES_PER_CHUNK as u64 + 3, 1));
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_parallel_count_mismatch() {
+        let text = random_match(2, 2).as_text();
+        for wrong in ["2 1", "2 3"] {
+            let text = text.replacen("2 2", wrong, 1);
+            assert_eq!(
+                ipmatch_text_parallel(&text).unwrap_err(),
+                ipmatch_text(&text).unwrap_err()
+            );
+        }
+    }
+}
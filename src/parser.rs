@@ -1,27 +1,155 @@
-use crate::data::{IPMatch, IPRecord, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::data::{IPMatch, IPRecord, MatchSide, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::error::{binary_error, text_error, ASPMatchError, MatchParseError, ParseResult};
+use crate::options::{
+    ByteOrder, DumpOptions, ParseLimits, ParseOptions, TextParseMode, TextWriteOptions,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::character::complete::{char, hex_digit0, hex_digit1, line_ending, one_of, space1};
+use nom::combinator::{all_consuming, eof, opt, recognize};
+use nom::error::{context, ErrorKind};
 use nom::multi::{count, separated_list0};
-use nom::number::complete::{le_f32, le_i32, le_u32, le_u64, le_u8};
-use nom::sequence::tuple;
-use nom::IResult;
-use std::fs::{File, OpenOptions};
+use nom::number::complete as number;
+use nom::number::complete::recognize_float;
+use nom::sequence::{pair, preceded, terminated, tuple};
+use nom::Parser;
+use std::fs;
+use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read, Write};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
-use nom::bytes::complete::take;
-use nom::character::complete::space1;
-use nom::combinator::verify;
-use thiserror::Error;
+/// Convert a u64 count read from `input` to usize, rejecting counts the host
+/// cannot address instead of truncating them (32 bits targets)
+pub(crate) fn usize_count<I>(
+    input: I,
+    field: &'static str,
+    value: u64,
+) -> Result<usize, nom::Err<MatchParseError<I>>> {
+    usize::try_from(value).map_err(|_| {
+        nom::Err::Error(
+            MatchParseError::new(input, ErrorKind::TooLarge)
+                .with_field(field)
+                .with_expected_found(format!("at most {}", usize::MAX), value),
+        )
+    })
+}
+
+/// Reject `value` of `field` above `max`, see [`ParseLimits`]
+pub(crate) fn check_limit<I>(
+    input: I,
+    field: &'static str,
+    value: u64,
+    max: u64,
+) -> Result<(), nom::Err<MatchParseError<I>>> {
+    if value <= max {
+        return Ok(());
+    }
+    Err(nom::Err::Error(
+        MatchParseError::new(input, ErrorKind::TooLarge)
+            .with_field(field)
+            .with_expected_found(format!("at most {}", max), value),
+    ))
+}
+
+/// Reject match content of `total_bytes` above `limits.max_total_bytes`
+pub(crate) fn check_total_bytes<I>(
+    input: I,
+    total_bytes: u64,
+    limits: &ParseLimits,
+) -> Result<(), nom::Err<MatchParseError<I>>> {
+    if total_bytes <= limits.max_total_bytes {
+        return Ok(());
+    }
+    Err(nom::Err::Error(
+        MatchParseError::new(input, ErrorKind::TooLarge).with_expected_found(
+            format!("at most {} bytes", limits.max_total_bytes),
+            "more bytes",
+        ),
+    ))
+}
+
+/// Bound `reader` to one byte past `limits.max_total_bytes`, enough for the
+/// parsers to report the limit without reading everything
+pub(crate) fn limited_reader<R: Read>(reader: R, limits: &ParseLimits) -> io::Take<R> {
+    reader.take(limits.max_total_bytes.saturating_add(1))
+}
+
+/// Parse a text float: decimal (`0.1`, `1e-3`), hexadecimal (`0x1.99999ap-4`),
+/// `nan`, `inf` or `infinity`, case insensitive and optionally signed
+///
+/// Decimal values are correctly rounded to the nearest f32, so the shortest
+/// representation written by [`IPRecord::as_text`] parses back bit-exact.
+pub(crate) fn text_float(input: &str) -> ParseResult<&str, f32> {
+    alt((hex_float, decimal_float))(input)
+        .map_err(|e| e.map(|_| MatchParseError::new(input, ErrorKind::Float)))
+}
+
+fn decimal_float(input: &str) -> ParseResult<&str, f32> {
+    let special = recognize(pair(
+        opt(one_of("+-")),
+        alt((
+            tag_no_case("infinity"),
+            tag_no_case("inf"),
+            tag_no_case("nan"),
+        )),
+    ));
+    let (i, text) = alt((special, recognize_float))(input)?;
+    match text.parse::<f32>() {
+        Ok(value) => Ok((i, value)),
+        Err(_) => Err(nom::Err::Error(MatchParseError::new(
+            input,
+            ErrorKind::Float,
+        ))),
+    }
+}
 
-/// Custom Error Enum for lib consumption.
-#[derive(Error, Debug)]
-pub enum ASPMatchError {
-    #[error("io error")]
-    IO(#[from] io::Error),
-    #[error("parser error")]
-    BinaryParser(#[from] nom::Err<nom::error::Error<Vec<u8>>>),
-    #[error("parser error")]
-    TextParser(#[from] nom::Err<nom::error::Error<String>>),
+fn hex_float(input: &str) -> ParseResult<&str, f32> {
+    let (i, (sign, _, integer, fraction, _, exponent)) = tuple((
+        opt(one_of("+-")),
+        tag_no_case("0x"),
+        hex_digit1,
+        opt(preceded(char('.'), hex_digit0)),
+        one_of("pP"),
+        nom::character::complete::i32,
+    ))(input)?;
+    let fraction = fraction.unwrap_or("");
+    let digits = format!("{}{}", integer, fraction);
+    let significant = digits.trim_start_matches('0');
+    let trimmed = significant.trim_end_matches('0');
+    let mut exponent = exponent as i64 - 4 * fraction.len() as i64
+        + 4 * (significant.len() - trimmed.len()) as i64;
+    // f64 holds 13 hex digits exactly, plenty for f32 significands
+    let significand = match trimmed.len() {
+        0 => 0,
+        1..=13 => u64::from_str_radix(trimmed, 16).expect("hex digits"),
+        _ => {
+            return Err(nom::Err::Error(MatchParseError::new(
+                input,
+                ErrorKind::TooLarge,
+            )))
+        }
+    };
+    let mut value = significand as f64;
+    // scale in steps, every intermediate stays a normal f64 while in f32 range
+    while exponent != 0 {
+        let step = exponent.clamp(-1000, 1000);
+        value *= 2f64.powi(step as i32);
+        exponent -= step;
+    }
+    let value = value as f32; // single rounding, significand * 2^exponent is exact in f64
+    Ok((i, if sign == Some('-') { -value } else { value }))
+}
+
+/// Parse a text field named `name` followed by at least one space
+fn text_field<'a, O, F>(
+    name: &'static str,
+    parser: F,
+) -> impl FnMut(&'a str) -> ParseResult<&'a str, O>
+where
+    F: Parser<&'a str, O, MatchParseError<&'a str>>,
+{
+    context(name, terminated(parser, space1))
 }
 
 /// Parse IPRecord from text assuming
@@ -38,48 +166,53 @@ pub enum ASPMatchError {
 /// assert!(remaining_text.is_empty());
 /// assert_eq!(parsed, record);
 /// ```
-pub fn iprecord_text(input: &str) -> IResult<&str, IPRecord> {
-    let (i, (x, _, y, _)) = tuple((
-        nom::number::complete::float,
-        space1, // x
-        nom::number::complete::float,
-        space1, // y
-    ))(input)?;
-    let (i, (xi, _, yi, _)) = tuple((
-        nom::character::complete::i32,
-        space1, // xi
-        nom::character::complete::i32,
-        space1, // yi
-    ))(i)?;
-    let (i, (orientation, _, scale, _, interest, _)) = tuple((
-        nom::number::complete::float,
-        space1, // orientation
-        nom::number::complete::float,
-        space1, // scale
-        nom::number::complete::float,
-        space1, // interest
-    ))(i)?;
-    let (i, (polarity, _)) = tuple((
-        nom::character::complete::u8,
-        space1, // polarity
-    ))(i)?;
-    let (i, (octave, _, scale_lvl, _)) = tuple((
-        nom::character::complete::u32,
-        space1, // octave
-        nom::character::complete::u32,
-        space1, // scale_lvl
-    ))(i)?;
-    let (i, (ndesc, _)) = tuple((
-        nom::character::complete::u64,
-        space1, // ndesc
-    ))(i)?;
-    let (i, desc) = verify(
-        separated_list0(space1, nom::number::complete::float),
-        |v: &[f32]| v.len() == ndesc as usize,
-    )(i)?;
+pub fn iprecord_text(input: &str) -> ParseResult<&str, IPRecord> {
+    iprecord_text_with_options(input, &ParseOptions::default())
+}
+
+/// Parse IPRecord from text in `options.text_mode`, within `options.limits`
+///
+/// In lenient mode, the record may end right after `ndesc` (trailing
+/// whitespace trimmed).
+///
+/// Returns remaining text
+pub fn iprecord_text_with_options<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPRecord> {
+    let (i, x) = text_field("x", text_float)(input)?;
+    let (i, y) = text_field("y", text_float)(i)?;
+    let (i, xi) = text_field("xi", nom::character::complete::i32)(i)?;
+    let (i, yi) = text_field("yi", nom::character::complete::i32)(i)?;
+    let (i, orientation) = text_field("orientation", text_float)(i)?;
+    let (i, scale) = text_field("scale", text_float)(i)?;
+    let (i, interest) = text_field("interest", text_float)(i)?;
+    let (i, polarity) = text_field("polarity", nom::character::complete::u8)(i)?;
+    let (i, octave) = text_field("octave", nom::character::complete::u32)(i)?;
+    let (i, scale_lvl) = text_field("scale_lvl", nom::character::complete::u32)(i)?;
+    let (i, ndesc) = match options.text_mode {
+        TextParseMode::Strict => text_field("ndesc", nom::character::complete::u64)(i)?,
+        // trailing whitespace was trimmed, e.g. `... 0` without descriptor
+        TextParseMode::Lenient => context(
+            "ndesc",
+            terminated(nom::character::complete::u64, alt((space1, eof))),
+        )(i)?,
+    };
+    check_limit(i, "ndesc", ndesc, options.limits.max_ndesc)?;
+    let (remaining, desc) = context("desc", separated_list0(space1, text_float))(i)?;
+    if desc.len() as u64 != ndesc {
+        return Err(nom::Err::Error(
+            MatchParseError::new(i, ErrorKind::Verify)
+                .with_field("desc")
+                .with_expected_found(
+                    format!("{} values (ndesc)", ndesc),
+                    format!("{} values", desc.len()),
+                ),
+        ));
+    }
 
     Ok((
-        i,
+        remaining,
         IPRecord {
             x,
             y,
@@ -109,14 +242,59 @@ pub fn iprecord_text(input: &str) -> IResult<&str, IPRecord> {
 /// assert!(remaining_bytes.is_empty());
 /// assert_eq!(parsed, record);
 /// ```
-pub fn iprecord(input: &[u8]) -> IResult<&[u8], IPRecord> {
-    let (i, (x, y)) = tuple((le_f32, le_f32))(input)?;
-    let (i, (xi, yi)) = tuple((le_i32, le_i32))(i)?;
-    let (i, (orientation, scale, interest)) = tuple((le_f32, le_f32, le_f32))(i)?;
-    let (i, polarity) = le_u8(i)?;
-    let (i, (octave, scale_lvl)) = tuple((le_u32, le_u32))(i)?;
-    let (i, ndesc) = le_u64(i)?;
-    let (i, desc) = count(le_f32, ndesc as usize)(i)?;
+pub fn iprecord(input: &[u8]) -> ParseResult<&[u8], IPRecord> {
+    iprecord_with_options(input, &ParseOptions::default())
+}
+
+/// Parse IPRecord from byte slice in `options.byte_order`
+///
+/// Returns remaining bytes
+///
+/// ```
+/// use aspmatch::{iprecord_with_options, ByteOrder, IPRecord, ParseOptions};
+/// let record = IPRecord { x: 1.0, ..IPRecord::default() };
+/// let bytes = record.as_bytes(ByteOrder::BigEndian);
+/// let options = ParseOptions { byte_order: ByteOrder::BigEndian, ..ParseOptions::default() };
+/// let (remaining_bytes, parsed) = iprecord_with_options(bytes.as_slice(), &options).unwrap();
+/// assert!(remaining_bytes.is_empty());
+/// assert_eq!(parsed, record);
+/// ```
+pub fn iprecord_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> ParseResult<&'a [u8], IPRecord> {
+    let e = options.byte_order.endianness();
+    let (i, x) = context("x", number::f32(e))(input)?;
+    let (i, y) = context("y", number::f32(e))(i)?;
+    let (i, xi) = context("xi", number::i32(e))(i)?;
+    let (i, yi) = context("yi", number::i32(e))(i)?;
+    let (i, orientation) = context("orientation", number::f32(e))(i)?;
+    let (i, scale) = context("scale", number::f32(e))(i)?;
+    let (i, interest) = context("interest", number::f32(e))(i)?;
+    let (i, polarity) = context("polarity", number::u8)(i)?;
+    let (i, octave) = context("octave", number::u32(e))(i)?;
+    let (i, scale_lvl) = context("scale_lvl", number::u32(e))(i)?;
+    let (i, ndesc) = context("ndesc", number::u64(e))(i)?;
+    check_limit(i, "ndesc", ndesc, options.limits.max_ndesc)?;
+    let ndesc = usize_count(i, "ndesc", ndesc)?;
+    // allocate descriptors only once their bytes are known to exist
+    if ndesc
+        .checked_mul(4)
+        .filter(|desc_size| *desc_size <= i.len())
+        .is_none()
+    {
+        // point at the first missing value
+        let available = i.len() / 4;
+        return Err(nom::Err::Error(
+            MatchParseError::new(&i[4 * available..], ErrorKind::Eof)
+                .with_field("desc")
+                .with_expected_found(
+                    format!("{} values (ndesc)", ndesc),
+                    format!("{} values", available),
+                ),
+        ));
+    }
+    let (i, desc) = context("desc", count(number::f32(e), ndesc))(i)?;
     Ok((
         i,
         IPRecord {
@@ -135,6 +313,56 @@ pub fn iprecord(input: &[u8]) -> IResult<&[u8], IPRecord> {
     ))
 }
 
+/// Side and index within side of the `index`-th record of a match
+pub(crate) fn record_location(size_1: u64, index: u64) -> (MatchSide, u64) {
+    if index < size_1 {
+        (MatchSide::Image1, index)
+    } else {
+        (MatchSide::Image2, index - size_1)
+    }
+}
+
+/// Parse text header `size_1 size_2`, returns `(size_1, size_1 + size_2)`
+pub(crate) fn ipmatch_text_header<'a>(
+    input: &'a str,
+    limits: &ParseLimits,
+) -> ParseResult<&'a str, (u64, u64)> {
+    check_total_bytes(input, input.len() as u64, limits)?;
+    let (i, size_1) = text_field("size_1", nom::character::complete::u64)(input)?;
+    let (i, size_2) = context(
+        "size_2",
+        terminated(nom::character::complete::u64, line_ending),
+    )(i)?;
+    let records = text_record_count(input, size_1, size_2, limits)?;
+    // `as_text` writes an empty line for an empty image 1
+    let (i, _) = if size_1 == 0 && size_2 > 0 {
+        opt(line_ending)(i)?
+    } else {
+        (i, None)
+    };
+    Ok((i, (size_1, records)))
+}
+
+/// Check header counts fit in memory, returns `size_1 + size_2`
+fn text_record_count<'a>(
+    input: &'a str,
+    size_1: u64,
+    size_2: u64,
+    limits: &ParseLimits,
+) -> Result<u64, nom::Err<MatchParseError<&'a str>>> {
+    usize_count(input, "size_1", size_1)?;
+    usize_count(input, "size_2", size_2)?;
+    let records = size_1.checked_add(size_2).ok_or_else(|| {
+        nom::Err::Error(
+            MatchParseError::new(input, ErrorKind::Verify)
+                .with_field("size_1 + size_2")
+                .with_expected_found("at most u64::MAX records", "more"),
+        )
+    })?;
+    check_limit(input, "size_1 + size_2", records, limits.max_records)?;
+    Ok(records)
+}
+
 /// Parse IPMatch from text assuming little endianness
 ///
 /// Returns remaining text
@@ -147,23 +375,183 @@ pub fn iprecord(input: &[u8]) -> IResult<&[u8], IPRecord> {
 /// let (remaining_text, parsed) = ipmatch_text(text.as_str()).unwrap();
 /// assert_eq!(parsed, _match);
 /// ```
-pub fn ipmatch_text(input: &str) -> IResult<&str, IPMatch> {
-    let (i, (size_1, _, size_2, _)) = tuple((
-        nom::character::complete::u64,
-        nom::character::complete::space1,
-        nom::character::complete::u64,
-        nom::character::complete::line_ending,
-    ))(input)?;
-    let (i, ip_records) = verify(
-        separated_list0(nom::character::complete::line_ending, iprecord_text),
-        |v: &[IPRecord]| v.len() == (size_1 + size_2) as usize,
-    )(i)?;
-    let (image_1_ip_records, image_2_ip_records) = ip_records.split_at(size_1 as usize);
+pub fn ipmatch_text(input: &str) -> ParseResult<&str, IPMatch> {
+    ipmatch_text_strict(input, &ParseOptions::default())
+}
+
+/// Parse IPMatch from text in strict mode within `options.limits`, returns
+/// remaining text
+fn ipmatch_text_strict<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPMatch> {
+    let (i, (size_1, records)) = ipmatch_text_header(input, &options.limits)?;
+    let (i, mut ip_records) = text_records(i, records, options, |e, index| {
+        let (side, record) = record_location(size_1, index);
+        e.with_record(side, record)
+    })?;
+    let image_2_ip_records = ip_records.split_off(size_1 as usize);
     Ok((
         i,
         IPMatch {
-            image_1: image_1_ip_records.to_vec(),
-            image_2: image_2_ip_records.to_vec(),
+            image_1: ip_records,
+            image_2: image_2_ip_records,
+        },
+    ))
+}
+
+/// Parse `records` text records, one per line, `locate` adds the record
+/// location to errors. Fails if more records follow.
+pub(crate) fn text_records<'a, F>(
+    mut i: &'a str,
+    records: u64,
+    options: &ParseOptions,
+    locate: F,
+) -> ParseResult<&'a str, Vec<IPRecord>>
+where
+    F: Fn(MatchParseError<&'a str>, u64) -> MatchParseError<&'a str>,
+{
+    let mut ip_records = vec![];
+    for index in 0..records {
+        let parsed = if index == 0 {
+            iprecord_text_with_options(i, options)
+        } else {
+            preceded(line_ending, |i| iprecord_text_with_options(i, options))(i)
+        };
+        let (remaining, ip_record) = parsed.map_err(|e| {
+            if i.is_empty() {
+                nom::Err::Error(MatchParseError::new(i, ErrorKind::Eof).with_expected_found(
+                    format!("{} records", records),
+                    format!("{} records", index),
+                ))
+            } else {
+                e.map(|e| locate(e, index))
+            }
+        })?;
+        ip_records.push(ip_record);
+        i = remaining;
+    }
+    if preceded(line_ending, iprecord_text)(i).is_ok() {
+        return Err(nom::Err::Error(
+            MatchParseError::new(i, ErrorKind::Verify)
+                .with_expected_found(format!("{} records", records), "more records"),
+        ));
+    }
+    Ok((i, ip_records))
+}
+
+/// Parse IPMatch from text in `options.text_mode`
+///
+/// Unlike [`ipmatch_text`], the whole input must be consumed: strict mode
+/// fails on trailing input after the last record instead of returning it.
+///
+/// ```
+/// use aspmatch::{ipmatch_text_with_options, IPMatch, IPRecord, ParseOptions, TextParseMode};
+/// let text = "# edited by hand\r\n1 0\r\n\t0 0 0 0 0 0 0 0 0 0 0  \r\n\r\n";
+/// let options = ParseOptions { text_mode: TextParseMode::Lenient, ..ParseOptions::default() };
+/// let (_, parsed) = ipmatch_text_with_options(text, &options).unwrap();
+/// assert_eq!(parsed.image_1, vec![IPRecord::default()]);
+/// assert!(ipmatch_text_with_options(text, &ParseOptions::default()).is_err());
+/// ```
+pub fn ipmatch_text_with_options<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPMatch> {
+    match options.text_mode {
+        TextParseMode::Strict => {
+            let (i, m) = ipmatch_text_strict(input, options)?;
+            Ok((end_of_text(i)?.0, m))
+        }
+        TextParseMode::Lenient => ipmatch_text_lenient(input, options),
+    }
+}
+
+/// Accept a single final line ending, fail on any other trailing input
+pub(crate) fn end_of_text(input: &str) -> ParseResult<&str, ()> {
+    let (i, _) = opt(line_ending)(input)?;
+    if i.is_empty() {
+        Ok((i, ()))
+    } else {
+        Err(nom::Err::Error(
+            MatchParseError::new(input, ErrorKind::Eof)
+                .with_expected_found("end of file", "trailing input"),
+        ))
+    }
+}
+
+/// Lines of `input` without `#` comments and surrounding whitespace, blank
+/// lines are skipped
+pub(crate) fn lenient_lines(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split('\n')
+        .map(|line| {
+            let line = line.split('#').next().unwrap_or(line);
+            line.trim_matches(|c| c == ' ' || c == '\t' || c == '\r')
+        })
+        .filter(|line| !line.is_empty())
+}
+
+/// Parse exactly `records` text records from `lines` of `input`, one per line,
+/// `locate` adds the record location to errors
+pub(crate) fn lenient_records<'a, L, F>(
+    input: &'a str,
+    lines: L,
+    records: u64,
+    options: &ParseOptions,
+    locate: F,
+) -> Result<Vec<IPRecord>, nom::Err<MatchParseError<&'a str>>>
+where
+    L: Iterator<Item = &'a str>,
+    F: Fn(MatchParseError<&'a str>, u64) -> MatchParseError<&'a str>,
+{
+    let mut ip_records = vec![];
+    for line in lines {
+        let index = ip_records.len() as u64;
+        if index == records {
+            return Err(nom::Err::Error(
+                MatchParseError::new(line, ErrorKind::Verify)
+                    .with_expected_found(format!("{} records", records), "more records"),
+            ));
+        }
+        let (_, ip_record) = all_consuming(|i| iprecord_text_with_options(i, options))(line)
+            .map_err(|e| e.map(|e| locate(e, index)))?;
+        ip_records.push(ip_record);
+    }
+    if (ip_records.len() as u64) < records {
+        return Err(nom::Err::Error(
+            MatchParseError::new(&input[input.len()..], ErrorKind::Eof).with_expected_found(
+                format!("{} records", records),
+                format!("{} records", ip_records.len()),
+            ),
+        ));
+    }
+    Ok(ip_records)
+}
+
+/// Parse IPMatch from text, skipping blank lines, `#` comments and whitespace
+/// around fields
+fn ipmatch_text_lenient<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> ParseResult<&'a str, IPMatch> {
+    check_total_bytes(input, input.len() as u64, &options.limits)?;
+    let mut lines = lenient_lines(input);
+    let header = lines.next().unwrap_or(&input[input.len()..]);
+    let (_, (size_1, size_2)) = all_consuming(pair(
+        text_field("size_1", nom::character::complete::u64),
+        context("size_2", nom::character::complete::u64),
+    ))(header)?;
+    let records = text_record_count(header, size_1, size_2, &options.limits)?;
+    let mut ip_records = lenient_records(input, lines, records, options, |e, index| {
+        let (side, record) = record_location(size_1, index);
+        e.with_record(side, record)
+    })?;
+    let image_2_ip_records = ip_records.split_off(size_1 as usize);
+    Ok((
+        &input[input.len()..],
+        IPMatch {
+            image_1: ip_records,
+            image_2: image_2_ip_records,
         },
     ))
 }
@@ -181,55 +569,254 @@ pub fn ipmatch_text(input: &str) -> IResult<&str, IPMatch> {
 /// assert!(remaining_bytes.is_empty());
 /// assert_eq!(parsed, _match);
 /// ```
-pub fn ipmatch(input: &[u8]) -> IResult<&[u8], IPMatch> {
-    let (i, header) = take(2 * 8_usize)(input)?;
-    let (_, (size_1, size_2)) = verify(tuple((le_u64, le_u64)), |(s1, s2)| {
-        (s1 + s2) <= (input.len() / MINIMUM_RECORD_SIZE_IN_BYTES as usize) as u64
-    })(header)?; // make sure size_1 and size_2 are reasonably sized
-    let (i, image_1_ip_records) = count(iprecord, size_1 as usize)(i)?;
-    let (i, image_2_ip_records) = count(iprecord, size_2 as usize)(i)?;
+pub fn ipmatch(input: &[u8]) -> ParseResult<&[u8], IPMatch> {
+    ipmatch_with_options(input, &ParseOptions::default())
+}
+
+/// Number of records announced by the header if `input_len` bytes can hold them
+pub(crate) fn checked_record_count(size_1: u64, size_2: u64, input_len: usize) -> Option<u64> {
+    let max_records = (input_len / MINIMUM_RECORD_SIZE_IN_BYTES as usize) as u64;
+    size_1
+        .checked_add(size_2)
+        .filter(|records| *records <= max_records)
+}
+
+/// Parse IPMatch from byte slice in `options.byte_order`
+///
+/// Returns remaining bytes
+///
+/// ```
+/// use aspmatch::{ipmatch_with_options, ByteOrder, IPMatch, ParseOptions};
+/// let _match = IPMatch::default();
+/// let bytes = _match.as_bytes(ByteOrder::BigEndian);
+/// let options = ParseOptions { byte_order: ByteOrder::BigEndian, ..ParseOptions::default() };
+/// let (remaining_bytes, parsed) = ipmatch_with_options(bytes.as_slice(), &options).unwrap();
+/// assert!(remaining_bytes.is_empty());
+/// assert_eq!(parsed, _match);
+/// ```
+pub fn ipmatch_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> ParseResult<&'a [u8], IPMatch> {
+    let e = options.byte_order.endianness();
+    check_total_bytes(input, input.len() as u64, &options.limits)?;
+    let (i, size_1) = context("size_1", number::u64(e))(input)?;
+    usize_count(input, "size_1", size_1)?;
+    let (i, size_2) = context("size_2", number::u64(e))(i)?;
+    usize_count(i, "size_2", size_2)?;
+    let max_records = options.limits.max_records;
+    check_limit(
+        input,
+        "size_1 + size_2",
+        size_1.saturating_add(size_2),
+        max_records,
+    )?;
+    // make sure size_1 and size_2 are reasonably sized
+    let records = checked_record_count(size_1, size_2, input.len()).ok_or_else(|| {
+        let max_records = input.len() / MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+        nom::Err::Error(
+            MatchParseError::new(input, ErrorKind::Verify)
+                .with_field("size_1 + size_2")
+                .with_expected_found(
+                    format!("at most {} records", max_records),
+                    format!("{} + {} records", size_1, size_2),
+                ),
+        )
+    })?;
+    let (i, mut ip_records) = binary_records(i, records, options, |e, index| {
+        let (side, record) = record_location(size_1, index);
+        e.with_record(side, record)
+    })?;
+    let image_2_ip_records = ip_records.split_off(size_1 as usize);
     Ok((
         i,
         IPMatch {
-            image_1: image_1_ip_records,
+            image_1: ip_records,
             image_2: image_2_ip_records,
         },
     ))
 }
 
-/// Parse IPMatch from text file assuming file fits in RAM
-pub fn parse_text_match_file(match_file: &File) -> Result<IPMatch, ASPMatchError> {
-    let mut buf_reader = BufReader::new(match_file);
-    let mut buf = String::new();
-    buf_reader.read_to_string(&mut buf)?;
-    let (_, m) = ipmatch_text(&buf).map_err(|e| ASPMatchError::TextParser(e.to_owned()))?;
+/// Parse `records` binary records, their count was checked against the input
+/// size, `locate` adds the record location to errors
+pub(crate) fn binary_records<'a, F>(
+    mut i: &'a [u8],
+    records: u64,
+    options: &ParseOptions,
+    locate: F,
+) -> ParseResult<&'a [u8], Vec<IPRecord>>
+where
+    F: Fn(MatchParseError<&'a [u8]>, u64) -> MatchParseError<&'a [u8]>,
+{
+    let mut ip_records = Vec::with_capacity(records as usize);
+    for index in 0..records {
+        let (remaining, ip_record) =
+            iprecord_with_options(i, options).map_err(|e| e.map(|e| locate(e, index)))?;
+        ip_records.push(ip_record);
+        i = remaining;
+    }
+    Ok((i, ip_records))
+}
+
+/// Parse IPMatch from text reader assuming content fits in RAM
+///
+/// ```
+/// use aspmatch::{parse_text_match_from_reader, IPMatch};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let text = IPMatch::default().as_text();
+/// let parsed = parse_text_match_from_reader(text.as_bytes())?;
+/// assert_eq!(parsed, IPMatch::default());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_text_match_from_reader<R: Read>(reader: R) -> Result<IPMatch, ASPMatchError> {
+    parse_text_match_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPMatch from text reader in `options.text_mode`, assuming content fits in RAM
+pub fn parse_text_match_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    let text = read_text(reader, &options.limits)?;
+    parse_text_match_str(&text, options)
+}
+
+/// Read UTF-8 text from `reader` within `limits.max_total_bytes`
+pub(crate) fn read_text<R: Read>(reader: R, limits: &ParseLimits) -> Result<String, ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(reader), limits);
+    let mut buf = vec![];
+    buf_reader.read_to_end(&mut buf)?;
+    // before UTF-8 decoding, the limit may have cut a character in half
+    if let Err(nom::Err::Error(e)) = check_total_bytes(buf.as_slice(), buf.len() as u64, limits) {
+        return Err(ASPMatchError::TextParser {
+            line: 1,
+            column: 1,
+            details: e.details,
+        });
+    }
+    let text = String::from_utf8(buf).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })?;
+    Ok(text)
+}
+
+/// Parse IPMatch from whole text content, strict mode runs in parallel if the
+/// `rayon` feature is enabled
+pub(crate) fn parse_text_match_str(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    let parsed = match options.text_mode {
+        TextParseMode::Strict => {
+            #[cfg(feature = "rayon")]
+            let parsed = crate::parallel::ipmatch_text_parallel_with_options(text, options);
+            #[cfg(not(feature = "rayon"))]
+            let parsed = ipmatch_text_strict(text, options);
+            parsed.and_then(|(i, m)| Ok((end_of_text(i)?.0, m)))
+        }
+        TextParseMode::Lenient => ipmatch_text_lenient(text, options),
+    };
+    let (_, m) = parsed.map_err(|e| text_error(text, e))?;
     Ok(m)
 }
 
-/// Parse IPMatch from binary file assuming file fits in RAM
-pub fn parse_binary_match_file(match_file: &File) -> Result<IPMatch, ASPMatchError> {
-    let mut buf_reader = BufReader::new(match_file);
+/// Parse IPMatch from binary reader assuming content fits in RAM
+///
+/// ```
+/// use aspmatch::{parse_binary_match_from_reader, IPMatch};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let bytes = IPMatch::default().as_le_bytes();
+/// let parsed = parse_binary_match_from_reader(bytes.as_slice())?;
+/// assert_eq!(parsed, IPMatch::default());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_binary_match_from_reader<R: Read>(reader: R) -> Result<IPMatch, ASPMatchError> {
+    parse_binary_match_from_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parse IPMatch from binary reader with options assuming content fits in RAM
+///
+/// ```
+/// use aspmatch::{parse_binary_match_from_reader_with_options, ByteOrder, IPMatch, ParseOptions};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let bytes = IPMatch::default().as_bytes(ByteOrder::BigEndian);
+/// let options = ParseOptions { byte_order: ByteOrder::BigEndian, ..ParseOptions::default() };
+/// let parsed = parse_binary_match_from_reader_with_options(bytes.as_slice(), &options)?;
+/// assert_eq!(parsed, IPMatch::default());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_binary_match_from_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<IPMatch, ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(reader), &options.limits);
     let mut buf = vec![];
     buf_reader.read_to_end(&mut buf)?;
-    let (_, m) = ipmatch(&buf).map_err(|e| ASPMatchError::BinaryParser(e.to_owned()))?;
+    let (_, m) = ipmatch_with_options(&buf, options).map_err(|e| binary_error(&buf, 0, e))?;
     Ok(m)
 }
 
+/// Parse IPMatch from text file assuming file fits in RAM
+pub fn parse_text_match_file(match_file: &File) -> Result<IPMatch, ASPMatchError> {
+    parse_text_match_from_reader(match_file)
+}
+
+/// Parse IPMatch from binary file assuming file fits in RAM
+pub fn parse_binary_match_file(match_file: &File) -> Result<IPMatch, ASPMatchError> {
+    parse_binary_match_from_reader(match_file)
+}
+
 /// Parse IPMatch from text file at path, assuming file fits in RAM
 pub fn parse_text_match_file_path<P: AsRef<Path>>(path: P) -> Result<IPMatch, ASPMatchError> {
+    parse_text_match_file_path_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPMatch from text file at path with options, assuming file fits in RAM
+pub fn parse_text_match_file_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<IPMatch, ASPMatchError> {
     let match_file = File::open(path)?;
-    parse_text_match_file(&match_file)
+    parse_text_match_from_reader_with_options(match_file, options)
 }
 
 /// Parse IPMatch from binary file at path, assuming file fits in RAM
 pub fn parse_binary_match_file_path<P: AsRef<Path>>(path: P) -> Result<IPMatch, ASPMatchError> {
+    parse_binary_match_file_path_with_options(path, &ParseOptions::default())
+}
+
+/// Parse IPMatch from binary file at path with options, assuming file fits in RAM
+pub fn parse_binary_match_file_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<IPMatch, ASPMatchError> {
     let match_file = File::open(path)?;
-    parse_binary_match_file(&match_file)
+    parse_binary_match_from_reader_with_options(match_file, options)
 }
 
 /// Dump IPMatch as text
 pub fn dump_match_as_text<W: Write>(ipmatch: &IPMatch, w: &mut W) -> Result<(), ASPMatchError> {
-    w.write_all(ipmatch.as_text().as_bytes())?;
+    dump_match_as_text_with_options(ipmatch, w, &TextWriteOptions::default())
+}
+
+/// Dump IPMatch as text, formatting floats according to `options`
+pub fn dump_match_as_text_with_options<W: Write>(
+    ipmatch: &IPMatch,
+    w: &mut W,
+    options: &TextWriteOptions,
+) -> Result<(), ASPMatchError> {
+    let mut w = BufWriter::new(w);
+    ipmatch.write_text_with_options(&mut w, options)?;
+    w.flush()?;
     Ok(())
 }
 
@@ -243,7 +830,18 @@ pub fn dump_match_as_text_to_file(
 
 /// Dump IPMatch as binary
 pub fn dump_match_as_binary<W: Write>(ipmatch: &IPMatch, w: &mut W) -> Result<(), ASPMatchError> {
-    w.write_all(ipmatch.as_le_bytes().as_slice())?;
+    dump_match_as_binary_with_byte_order(ipmatch, w, ByteOrder::LittleEndian)
+}
+
+/// Dump IPMatch as binary in given byte order
+pub fn dump_match_as_binary_with_byte_order<W: Write>(
+    ipmatch: &IPMatch,
+    w: &mut W,
+    byte_order: ByteOrder,
+) -> Result<(), ASPMatchError> {
+    let mut w = BufWriter::new(w);
+    ipmatch.write_bytes(&mut w, byte_order)?;
+    w.flush()?;
     Ok(())
 }
 
@@ -255,32 +853,120 @@ pub fn dump_match_as_binary_to_file(
     dump_match_as_binary(ipmatch, match_file)
 }
 
-/// Dump IPMatch as text to file at path
+/// Write a file at `path` atomically: `write` fills a temporary file in the
+/// same directory which is then synced and renamed over `path`
+///
+/// The file keeps the permissions of the file it replaces, a new file gets the
+/// permissions of `File::create` (`0o666` minus the umask on unix).
+pub(crate) fn dump_to_path_atomically<P, F>(
+    path: P,
+    options: &DumpOptions,
+    write: F,
+) -> Result<(), ASPMatchError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut File) -> Result<(), ASPMatchError>,
+{
+    let path = path.as_ref();
+    if !options.overwrite && path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        )
+        .into());
+    }
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    // temporary files are created 0600 otherwise
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let mut tmpfile = builder.tempfile_in(directory)?;
+    write(tmpfile.as_file_mut())?;
+    if let Ok(metadata) = fs::metadata(path) {
+        tmpfile.as_file().set_permissions(metadata.permissions())?;
+    }
+    tmpfile.as_file().sync_all()?;
+    if options.overwrite {
+        tmpfile.persist(path).map_err(|e| e.error)?;
+    } else {
+        tmpfile.persist_noclobber(path).map_err(|e| e.error)?;
+    }
+    #[cfg(unix)]
+    File::open(directory)?.sync_all()?; // make the rename itself durable
+    Ok(())
+}
+
+/// Dump IPMatch as text to file at path, replacing it atomically
 pub fn dump_match_as_text_to_path<P: AsRef<Path>>(
     ipmatch: &IPMatch,
     path: P,
 ) -> Result<(), ASPMatchError> {
-    let mut match_file = OpenOptions::new().write(true).create(true).open(path)?;
-    dump_match_as_text_to_file(ipmatch, &mut match_file)
+    dump_match_as_text_to_path_with_options(ipmatch, path, &DumpOptions::default())
 }
 
-/// Dump IPMatch as binary to file at path
+/// Dump IPMatch as text to file at path atomically, following `options`
+pub fn dump_match_as_text_to_path_with_options<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+    options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    dump_to_path_atomically(path, options, |match_file| {
+        dump_match_as_text_with_options(ipmatch, match_file, &options.text)
+    })
+}
+
+/// Dump IPMatch as binary to file at path, replacing it atomically
 pub fn dump_match_as_binary_to_path<P: AsRef<Path>>(
     ipmatch: &IPMatch,
     path: P,
 ) -> Result<(), ASPMatchError> {
-    let mut match_file = OpenOptions::new().write(true).create(true).open(path)?;
-    dump_match_as_binary_to_file(ipmatch, &mut match_file)
+    dump_match_as_binary_to_path_with_options(ipmatch, path, &DumpOptions::default())
+}
+
+/// Dump IPMatch as binary to file at path atomically, following `options`
+///
+/// ```
+/// use aspmatch::{dump_match_as_binary_to_path_with_options, DumpOptions, IPMatch};
+/// let directory = tempfile::tempdir().unwrap();
+/// let path = directory.path().join("run-a__b.match");
+/// let create_new = DumpOptions { overwrite: false, ..DumpOptions::default() };
+/// assert!(dump_match_as_binary_to_path_with_options(&IPMatch::default(), &path, &create_new).is_ok());
+/// assert!(dump_match_as_binary_to_path_with_options(&IPMatch::default(), &path, &create_new).is_err());
+/// ```
+pub fn dump_match_as_binary_to_path_with_options<P: AsRef<Path>>(
+    ipmatch: &IPMatch,
+    path: P,
+    options: &DumpOptions,
+) -> Result<(), ASPMatchError> {
+    dump_to_path_atomically(path, options, |match_file| {
+        dump_match_as_binary_to_file(ipmatch, match_file)
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::data::write_float;
     use crate::data::{IPMatch, IPRecord};
+    use crate::options::{
+        ByteOrder, DumpOptions, FloatFormat, ParseLimits, ParseOptions, TextParseMode,
+        TextWriteOptions,
+    };
     use crate::parser::{
-        dump_match_as_text_to_file, ipmatch, ipmatch_text, iprecord, iprecord_text,
-        parse_text_match_file,
+        dump_match_as_binary_to_path, dump_match_as_binary_to_path_with_options,
+        dump_match_as_text_to_file, dump_match_as_text_to_path,
+        dump_match_as_text_to_path_with_options, ipmatch, ipmatch_text, ipmatch_text_with_options,
+        ipmatch_with_options, iprecord, iprecord_text, parse_binary_match_file_path,
+        parse_text_match_file, parse_text_match_file_path, text_float, usize_count,
+    };
+    use crate::{
+        dump_match_as_binary_to_file, parse_binary_match_file, parse_binary_match_from_reader,
+        parse_binary_match_from_reader_with_options, parse_text_match_from_reader,
+        parse_text_match_from_reader_with_options, ASPMatchError, MatchSide,
     };
-    use crate::{dump_match_as_binary_to_file, parse_binary_match_file};
+    use std::io;
     use std::io::{Seek, SeekFrom};
 
     fn dummy_iprecord() -> IPRecord {
@@ -343,6 +1029,40 @@ mod tests {
         assert_eq!(ipmatch, expected);
     }
 
+    #[test]
+    fn test_ipmatch_u64_header() {
+        let expected = dummy_ipmatch();
+        let bytes = expected.as_le_bytes();
+        assert_eq!(bytes[..8], 1u64.to_le_bytes());
+        assert_eq!(bytes[8..16], 2u64.to_le_bytes());
+        let bytes = expected.as_bytes(ByteOrder::BigEndian);
+        assert_eq!(bytes[..8], 1u64.to_be_bytes());
+        assert_eq!(bytes[8..16], 2u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_usize_count() {
+        assert_eq!(usize_count(&b""[..], "size_1", 42), Ok(42));
+        let too_large = usize_count(&b""[..], "size_1", u64::MAX);
+        assert_eq!(too_large.is_err(), usize::BITS < u64::BITS);
+    }
+
+    #[test]
+    fn test_ipmatch_big_endian() {
+        let expected = dummy_ipmatch();
+        let input: Vec<u8> = expected.as_bytes(ByteOrder::BigEndian);
+        assert_ne!(input, expected.as_le_bytes());
+        let options = ParseOptions {
+            byte_order: ByteOrder::BigEndian,
+            ..ParseOptions::default()
+        };
+        let (i, ipmatch) = ipmatch_with_options(&input, &options).unwrap();
+        assert!(i.is_empty());
+        assert_eq!(ipmatch, expected);
+        // wrong byte order is rejected by the header size check
+        assert!(ipmatch_with_options(&expected.as_le_bytes(), &options).is_err());
+    }
+
     #[test]
     fn test_dump_and_parse_text_match_file() {
         let expected = dummy_ipmatch();
@@ -368,4 +1088,409 @@ mod tests {
         assert!(parse.is_ok());
         assert_eq!(parse.unwrap(), expected);
     }
+
+    #[test]
+    fn test_text_parser_error_location() {
+        let mut text = dummy_ipmatch().as_text();
+        // image 2, record 1: ndesc 2 -> 3 while only 2 values follow
+        let line_start = text.rfind('\n').unwrap() + 1;
+        let ndesc = line_start + text[line_start..].find(" 2 44").unwrap() + 1;
+        text.replace_range(ndesc..ndesc + 1, "3");
+        match parse_text_match_from_reader(text.as_bytes()) {
+            Err(ASPMatchError::TextParser {
+                line,
+                column,
+                details,
+            }) => {
+                assert_eq!(line, 4);
+                assert_eq!(column as usize, ndesc - line_start + 3);
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("desc"));
+                assert_eq!(details.expected.as_deref(), Some("3 values (ndesc)"));
+                assert_eq!(details.found.as_deref(), Some("2 values"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_binary_parser_error_location() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let truncated = &bytes[..bytes.len() - 2];
+        match parse_binary_match_from_reader(truncated) {
+            Err(ASPMatchError::BinaryParser { offset, details }) => {
+                // last descriptor value of image 2 record 1
+                assert_eq!(offset as usize, bytes.len() - 4);
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("desc"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let mut bytes = dummy_ipmatch().as_le_bytes();
+        bytes[0] = 42; // size_1
+        match parse_binary_match_from_reader(bytes.as_slice()) {
+            Err(ASPMatchError::BinaryParser { offset, details }) => {
+                assert_eq!(offset, 0);
+                assert_eq!(details.side, None);
+                assert_eq!(details.field, Some("size_1 + size_2"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dump_to_path_replaces_larger_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("match.bin");
+        dump_match_as_binary_to_path(&dummy_ipmatch(), &path).unwrap();
+        let smaller = IPMatch {
+            image_1: vec![dummy_iprecord()],
+            image_2: vec![],
+        };
+        dump_match_as_binary_to_path(&smaller, &path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), smaller.as_le_bytes());
+        dump_match_as_text_to_path(&smaller, &path).unwrap();
+        assert_eq!(parse_text_match_file_path(&path).unwrap(), smaller);
+        // only the target remains, no stale temporary file
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_dump_to_path_without_overwrite() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("match.bin");
+        let options = DumpOptions {
+            overwrite: false,
+            ..DumpOptions::default()
+        };
+        dump_match_as_binary_to_path_with_options(&dummy_ipmatch(), &path, &options).unwrap();
+        match dump_match_as_text_to_path_with_options(&IPMatch::default(), &path, &options) {
+            Err(ASPMatchError::IO(e)) => assert_eq!(e.kind(), io::ErrorKind::AlreadyExists),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(
+            parse_binary_match_file_path(&path).unwrap(),
+            dummy_ipmatch()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dump_to_path_permissions() {
+        use std::fs::File;
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::Path;
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let directory = tempfile::tempdir().unwrap();
+        // File::create applies the umask
+        let reference = directory.path().join("reference");
+        File::create(&reference).unwrap();
+        let path = directory.path().join("match.bin");
+        dump_match_as_binary_to_path(&dummy_ipmatch(), &path).unwrap();
+        assert_eq!(mode(&path), mode(&reference));
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        dump_match_as_text_to_path(&dummy_ipmatch(), &path).unwrap();
+        assert_eq!(mode(&path), 0o640);
+    }
+
+    fn float_round_trip(value: f32, format: FloatFormat) -> f32 {
+        let mut text = vec![];
+        write_float(&mut text, value, format).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let (remaining, parsed) = text_float(&text).unwrap();
+        assert!(remaining.is_empty(), "{} not fully parsed", text);
+        parsed
+    }
+
+    fn assert_float_round_trip(bits: u32) {
+        let value = f32::from_bits(bits);
+        for format in [FloatFormat::Shortest, FloatFormat::Hex] {
+            let parsed = float_round_trip(value, format);
+            if value.is_nan() {
+                assert!(parsed.is_nan());
+            } else {
+                assert_eq!(parsed.to_bits(), bits, "{:?} {:?}", value, format);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_float_round_trip() {
+        let edges = [
+            0.0,
+            -0.0,
+            f32::MIN_POSITIVE,
+            f32::from_bits(1),           // smallest subnormal
+            f32::from_bits(0x007f_ffff), // largest subnormal
+            f32::MAX,
+            f32::MIN,
+            f32::EPSILON,
+            0.1,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+        ];
+        for value in edges {
+            assert_float_round_trip(value.to_bits());
+        }
+        // sweep the whole f32 range, both signs, every exponent
+        for bits in (0..=u32::MAX).step_by(65_521) {
+            assert_float_round_trip(bits);
+        }
+    }
+
+    #[test]
+    #[ignore = "exhaustive, run with `cargo test --release -- --ignored`"]
+    fn test_text_float_round_trip_exhaustive() {
+        for bits in 0..=u32::MAX {
+            assert_float_round_trip(bits);
+        }
+    }
+
+    #[test]
+    fn test_text_float_spellings() {
+        let parse = |text| text_float(text).unwrap().1;
+        assert!(parse("NaN").is_nan());
+        assert!(parse("-nan").is_nan());
+        assert_eq!(parse("inf"), f32::INFINITY);
+        assert_eq!(parse("+Infinity"), f32::INFINITY);
+        assert_eq!(parse("-INF"), f32::NEG_INFINITY);
+        assert_eq!(parse("1e-3"), 0.001);
+        assert_eq!(parse("-0x1.8p1"), -3.0);
+        assert_eq!(parse("0x0.000002p-126"), f32::from_bits(1));
+        assert_eq!(parse("0X10P-4"), 1.0);
+        assert_eq!(parse("0x1.000000000000000000p0"), 1.0);
+        assert!(text_float("x").is_err());
+    }
+
+    #[test]
+    fn test_text_float_fixed() {
+        let mut text = vec![];
+        write_float(&mut text, 0.1, FloatFormat::Fixed(3)).unwrap();
+        assert_eq!(text, b"0.100");
+        assert_eq!(float_round_trip(0.1, FloatFormat::Fixed(3)), 0.1);
+        assert_eq!(float_round_trip(0.1234, FloatFormat::Fixed(2)), 0.12);
+    }
+
+    #[test]
+    fn test_dump_text_with_hex_floats() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("match.txt");
+        let mut ipmatch = dummy_ipmatch();
+        ipmatch.image_1[0].x = 0.1;
+        ipmatch.image_2[0].desc = vec![f32::MIN_POSITIVE, -0.0, f32::NEG_INFINITY];
+        let options = DumpOptions {
+            text: TextWriteOptions {
+                float_format: FloatFormat::Hex,
+            },
+            ..DumpOptions::default()
+        };
+        dump_match_as_text_to_path_with_options(&ipmatch, &path, &options).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("0x1.99999ap-4 "));
+        assert_eq!(parse_text_match_file_path(&path).unwrap(), ipmatch);
+    }
+
+    fn lenient() -> ParseOptions {
+        ParseOptions {
+            text_mode: TextParseMode::Lenient,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_ipmatch_text_strict_trailing_input() {
+        let ipmatch = dummy_ipmatch();
+        let text = ipmatch.as_text();
+        let strict = ParseOptions::default();
+        for accepted in [text.clone(), format!("{}\n", text), format!("{}\r\n", text)] {
+            let (i, parsed) = ipmatch_text_with_options(&accepted, &strict).unwrap();
+            assert!(i.is_empty());
+            assert_eq!(parsed, ipmatch);
+        }
+        let trailing = format!("{}\n\n", text);
+        assert!(ipmatch_text_with_options(&trailing, &strict).is_err());
+        let garbage = format!("{} garbage", text);
+        match parse_text_match_from_reader(garbage.as_bytes()) {
+            Err(ASPMatchError::TextParser { line, details, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(details.found.as_deref(), Some("trailing input"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ipmatch_text_empty_image_1() {
+        let ipmatch = IPMatch {
+            image_1: vec![],
+            image_2: vec![dummy_iprecord()],
+        };
+        let text = ipmatch.as_text();
+        for options in [ParseOptions::default(), lenient()] {
+            let (_, parsed) = ipmatch_text_with_options(&text, &options).unwrap();
+            assert_eq!(parsed, ipmatch);
+        }
+    }
+
+    #[test]
+    fn test_ipmatch_text_lenient() {
+        let ipmatch = IPMatch {
+            image_1: vec![dummy_iprecord(), IPRecord::default()],
+            image_2: vec![IPRecord::default(), dummy_iprecord()],
+        };
+        let text = format!(
+            "# matches exported by hand\r\n\r\n  2\t2  # image 1, image 2\r\n{}\n\n",
+            ipmatch
+                .as_text()
+                .lines()
+                .skip(1)
+                .map(|line| format!("\t{}  # comment\r", line.replace(' ', "  ")))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        assert!(ipmatch_text_with_options(&text, &ParseOptions::default()).is_err());
+        let (i, parsed) = ipmatch_text_with_options(&text, &lenient()).unwrap();
+        assert!(i.is_empty());
+        assert_eq!(parsed, ipmatch);
+    }
+
+    #[test]
+    fn test_ipmatch_text_lenient_errors() {
+        let text = dummy_ipmatch().as_text();
+        let more = format!("{}\n{}", text, dummy_iprecord().as_text());
+        let fewer: String = text.lines().take(3).collect::<Vec<_>>().join("\n");
+        let broken = text.replacen("43 ", "x ", 1);
+        for (input, location, expected) in [
+            (
+                more,
+                "line 5, column 1",
+                "expected 3 records, found more records",
+            ),
+            (
+                fewer,
+                "line 3, column ",
+                "expected 3 records, found 2 records",
+            ),
+            (
+                broken,
+                "line 2, column 4",
+                "image 1 record 0, field `y`, Float",
+            ),
+        ] {
+            let e = parse_text_match_from_reader_with_options(input.as_bytes(), &lenient())
+                .unwrap_err()
+                .to_string();
+            assert!(e.contains(location), "{}", e);
+            assert!(e.ends_with(expected), "{}", e);
+        }
+    }
+
+    fn limited(limits: ParseLimits) -> ParseOptions {
+        ParseOptions {
+            limits,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let ipmatch = dummy_ipmatch();
+        let bytes = ipmatch.as_le_bytes();
+        let text = ipmatch.as_text();
+        let exact = limited(ParseLimits {
+            max_ndesc: 2,
+            max_records: 3,
+            max_total_bytes: bytes.len().max(text.len()) as u64,
+        });
+        assert_eq!(ipmatch_with_options(&bytes, &exact).unwrap().1, ipmatch);
+        assert_eq!(ipmatch_text_with_options(&text, &exact).unwrap().1, ipmatch);
+        let too_small = [
+            (
+                "ndesc",
+                ParseLimits {
+                    max_ndesc: 1,
+                    ..ParseLimits::default()
+                },
+            ),
+            (
+                "size_1 + size_2",
+                ParseLimits {
+                    max_records: 2,
+                    ..ParseLimits::default()
+                },
+            ),
+        ];
+        for (field, limits) in too_small {
+            let options = limited(limits);
+            match ipmatch_with_options(&bytes, &options) {
+                Err(nom::Err::Error(e)) => {
+                    assert_eq!(e.details.kind, nom::error::ErrorKind::TooLarge);
+                    assert_eq!(e.details.field, Some(field));
+                }
+                e => panic!("unexpected result {:?}", e),
+            }
+            match parse_binary_match_from_reader_with_options(bytes.as_slice(), &options) {
+                Err(ASPMatchError::BinaryParser { details, .. }) => {
+                    assert_eq!(details.field, Some(field))
+                }
+                e => panic!("unexpected result {:?}", e),
+            }
+            let e = parse_text_match_from_reader_with_options(text.as_bytes(), &options);
+            match e {
+                Err(ASPMatchError::TextParser { details, .. }) => {
+                    assert_eq!(details.field, Some(field))
+                }
+                e => panic!("unexpected result {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_limits_total_bytes() {
+        let options = limited(ParseLimits {
+            max_total_bytes: 1024,
+            ..ParseLimits::default()
+        });
+        // endless input, reading stops right after the limit
+        let endless = io::repeat(0);
+        match parse_binary_match_from_reader_with_options(endless, &options) {
+            Err(ASPMatchError::BinaryParser { offset: 0, details }) => {
+                assert_eq!(details.expected.as_deref(), Some("at most 1024 bytes"))
+            }
+            e => panic!("unexpected result {:?}", e),
+        }
+        let endless = io::repeat(b'1');
+        match parse_text_match_from_reader_with_options(endless, &options) {
+            Err(ASPMatchError::TextParser {
+                line: 1, details, ..
+            }) => {
+                assert_eq!(details.expected.as_deref(), Some("at most 1024 bytes"))
+            }
+            e => panic!("unexpected result {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_hostile_ndesc() {
+        // a single record claiming 2^60 descriptor values, 4 bytes short
+        let mut bytes = IPMatch {
+            image_1: vec![IPRecord::default()],
+            image_2: vec![],
+        }
+        .as_le_bytes();
+        let ndesc_offset = bytes.len() - 8;
+        bytes[ndesc_offset..].copy_from_slice(&(1u64 << 60).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4 * 16]);
+        match ipmatch(&bytes) {
+            Err(nom::Err::Error(e)) => {
+                assert_eq!(e.details.kind, nom::error::ErrorKind::Eof);
+                assert_eq!(e.details.field, Some("desc"));
+            }
+            e => panic!("unexpected result {:?}", e),
+        }
+    }
 }
@@ -0,0 +1,318 @@
+use crate::data::{IPRecord, MatchSide, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::error::{binary_error, ASPMatchError};
+use crate::options::ParseOptions;
+use crate::parser::{check_limit, check_total_bytes, iprecord_with_options, usize_count};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+const HEADER_SIZE_IN_BYTES: usize = 2 * 8;
+
+/// Streaming reader over a binary match file.
+///
+/// Parses the 16 bytes header then yields records one at a time: first the
+/// `size_1` records of image 1, then the `size_2` records of image 2.
+/// Only one record is held in memory at a time.
+///
+/// ```
+/// use aspmatch::{IPMatch, IPRecord, MatchReader, MatchSide};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let ipmatch = IPMatch {
+///     image_1: vec![IPRecord::default()],
+///     image_2: vec![IPRecord::default(), IPRecord::default()],
+/// };
+/// let bytes = ipmatch.as_le_bytes();
+/// let reader = MatchReader::new(bytes.as_slice())?;
+/// assert_eq!((reader.size_1(), reader.size_2()), (1, 2));
+/// let sides = reader
+///     .map(|r| r.map(|(side, _record)| side))
+///     .collect::<Result<Vec<MatchSide>, ASPMatchError>>()?;
+/// assert_eq!(sides, vec![MatchSide::Image1, MatchSide::Image2, MatchSide::Image2]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MatchReader<R> {
+    reader: R,
+    options: ParseOptions,
+    size_1: u64,
+    size_2: u64,
+    records_read: u64,
+    position: u64,
+    failed: bool,
+    buf: Vec<u8>,
+}
+
+impl MatchReader<BufReader<File>> {
+    /// Open binary match file at path for streaming
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ASPMatchError> {
+        let match_file = File::open(path)?;
+        MatchReader::new(BufReader::new(match_file))
+    }
+}
+
+impl<R: Read> MatchReader<R> {
+    /// Read the match header from `reader`, records are read lazily
+    pub fn new(reader: R) -> Result<Self, ASPMatchError> {
+        MatchReader::with_options(reader, &ParseOptions::default())
+    }
+
+    /// Read the match header from `reader` with options, records are read lazily
+    pub fn with_options(mut reader: R, options: &ParseOptions) -> Result<Self, ASPMatchError> {
+        let mut header = [0u8; HEADER_SIZE_IN_BYTES];
+        reader.read_exact(&mut header)?;
+        let size_1 = options.byte_order.u64_at(&header, 0);
+        let size_2 = options.byte_order.u64_at(&header, 8);
+        usize_count(&header[..], "size_1", size_1).map_err(|e| binary_error(&header, 0, e))?;
+        usize_count(&header[8..], "size_2", size_2).map_err(|e| binary_error(&header, 0, e))?;
+        let records = size_1.saturating_add(size_2);
+        check_limit(
+            &header[..],
+            "size_1 + size_2",
+            records,
+            options.limits.max_records,
+        )
+        .map_err(|e| binary_error(&header, 0, e))?;
+        Ok(MatchReader {
+            reader,
+            options: options.clone(),
+            size_1,
+            size_2,
+            records_read: 0,
+            position: HEADER_SIZE_IN_BYTES as u64,
+            failed: false,
+            buf: Vec::with_capacity(MINIMUM_RECORD_SIZE_IN_BYTES as usize),
+        })
+    }
+
+    /// Number of image 1 records announced by the header
+    pub fn size_1(&self) -> u64 {
+        self.size_1
+    }
+
+    /// Number of image 2 records announced by the header
+    pub fn size_2(&self) -> u64 {
+        self.size_2
+    }
+
+    /// Side of the next record to be read, `None` once all records were read
+    pub fn side(&self) -> Option<MatchSide> {
+        if self.records_read < self.size_1 {
+            Some(MatchSide::Image1)
+        } else if self.records_read - self.size_1 < self.size_2 {
+            Some(MatchSide::Image2)
+        } else {
+            None
+        }
+    }
+
+    /// Unwrap the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_record(&mut self, side: MatchSide) -> Result<IPRecord, ASPMatchError> {
+        let index = match side {
+            MatchSide::Image1 => self.records_read,
+            MatchSide::Image2 => self.records_read - self.size_1,
+        };
+        let fixed_size = MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+        self.buf.clear();
+        self.reader
+            .by_ref()
+            .take(fixed_size as u64)
+            .read_to_end(&mut self.buf)?;
+        if self.buf.len() == fixed_size {
+            // ndesc is the last field of the fixed size part of the record
+            let ndesc_bytes = &self.buf[fixed_size - 8..];
+            let ndesc = self.options.byte_order.u64_at(ndesc_bytes, 0);
+            let desc_size = ndesc.saturating_mul(4);
+            let limits = &self.options.limits;
+            let record_end = (self.position + fixed_size as u64).saturating_add(desc_size);
+            check_limit(ndesc_bytes, "ndesc", ndesc, limits.max_ndesc)
+                .and_then(|_| check_total_bytes(&self.buf[..], record_end, limits))
+                .map_err(|e| {
+                    let e = e.map(|e| e.with_record(side, index));
+                    binary_error(&self.buf, self.position, e)
+                })?;
+            // `take` grows the buffer as bytes arrive instead of trusting `ndesc`
+            self.reader
+                .by_ref()
+                .take(desc_size)
+                .read_to_end(&mut self.buf)?;
+        }
+        // a truncated record fails to parse at its first missing field
+        let (_, record) = iprecord_with_options(&self.buf, &self.options).map_err(|e| {
+            let e = e.map(|e| e.with_record(side, index));
+            binary_error(&self.buf, self.position, e)
+        })?;
+        self.position += self.buf.len() as u64;
+        Ok(record)
+    }
+}
+
+impl<R: Read> Iterator for MatchReader<R> {
+    type Item = Result<(MatchSide, IPRecord), ASPMatchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let side = self.side()?;
+        match self.read_record(side) {
+            Ok(record) => {
+                self.records_read += 1;
+                Some(Ok((side, record)))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.failed {
+            return (0, Some(0));
+        }
+        // sizes come from an untrusted header
+        let remaining = self
+            .size_1
+            .saturating_add(self.size_2)
+            .saturating_sub(self.records_read);
+        (0, usize::try_from(remaining).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::reader::MatchReader;
+    use crate::{ASPMatchError, ByteOrder, ParseLimits, ParseOptions};
+
+    fn dummy_ipmatch() -> IPMatch {
+        let record = |x: f32, ndesc: usize| IPRecord {
+            x,
+            y: x + 1.0,
+            desc: vec![x; ndesc],
+            ..IPRecord::default()
+        };
+        IPMatch {
+            image_1: vec![record(1.0, 2), record(2.0, 0)],
+            image_2: vec![record(3.0, 1), record(4.0, 3)],
+        }
+    }
+
+    #[test]
+    fn test_match_reader() {
+        let expected = dummy_ipmatch();
+        let bytes = expected.as_le_bytes();
+        let reader = MatchReader::new(bytes.as_slice()).unwrap();
+        let mut parsed = IPMatch::default();
+        for item in reader {
+            match item.unwrap() {
+                (MatchSide::Image1, record) => parsed.image_1.push(record),
+                (MatchSide::Image2, record) => parsed.image_2.push(record),
+            }
+        }
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_match_reader_big_endian() {
+        let expected = dummy_ipmatch();
+        let bytes = expected.as_bytes(ByteOrder::BigEndian);
+        let options = ParseOptions {
+            byte_order: ByteOrder::BigEndian,
+            ..ParseOptions::default()
+        };
+        let reader = MatchReader::with_options(bytes.as_slice(), &options).unwrap();
+        assert_eq!((reader.size_1(), reader.size_2()), (2, 2));
+        let records: Vec<IPRecord> = reader.map(|item| item.unwrap().1).collect();
+        assert_eq!(records[..2], expected.image_1[..]);
+        assert_eq!(records[2..], expected.image_2[..]);
+    }
+
+    #[test]
+    fn test_match_reader_truncated() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let truncated = &bytes[..bytes.len() - 1];
+        let reader = MatchReader::new(truncated).unwrap();
+        let items: Vec<_> = reader.collect();
+        assert_eq!(items.len(), 4);
+        assert!(items[..3].iter().all(|item| item.is_ok()));
+        match &items[3] {
+            Err(ASPMatchError::BinaryParser { offset, details }) => {
+                // last descriptor value of image 2 record 1
+                assert_eq!(*offset as usize, bytes.len() - 4);
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("desc"));
+            }
+            item => panic!("unexpected item {:?}", item),
+        }
+        // cut in the fixed size part of the first record, after `x`
+        let reader = MatchReader::new(&bytes[..16 + 6]).unwrap();
+        match reader.last() {
+            Some(Err(ASPMatchError::BinaryParser { offset, details })) => {
+                assert_eq!(offset, 16 + 4);
+                assert_eq!(details.side, Some(MatchSide::Image1));
+                assert_eq!(details.field, Some("y"));
+            }
+            item => panic!("unexpected item {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_match_reader_missing_header() {
+        let bytes = [0u8; 8];
+        assert!(MatchReader::new(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_match_reader_hostile_header() {
+        let bytes = [0xffu8; 16];
+        let reader = MatchReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.size_hint(), (0, Some(usize::MAX)));
+        let mut items = vec![];
+        items.extend(reader);
+        assert_eq!(items.len(), 1);
+        assert!(matches!(
+            items[0],
+            Err(ASPMatchError::BinaryParser { offset: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn test_match_reader_limits() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_records: 3,
+                ..ParseLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert!(MatchReader::with_options(bytes.as_slice(), &options).is_err());
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_ndesc: 2,
+                ..ParseLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let reader = MatchReader::with_options(bytes.as_slice(), &options).unwrap();
+        let items: Vec<_> = reader.collect();
+        // the fourth record has 3 descriptor values
+        assert_eq!(items.len(), 4);
+        match &items[3] {
+            Err(ASPMatchError::BinaryParser { offset, details }) => {
+                // the ndesc field of image 2 record 1
+                assert_eq!(*offset as usize, bytes.len() - 3 * 4 - 8);
+                assert_eq!(details.field, Some("ndesc"));
+                assert_eq!(details.record, Some(1));
+            }
+            item => panic!("unexpected item {:?}", item),
+        }
+    }
+}
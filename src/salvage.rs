@@ -0,0 +1,244 @@
+use crate::data::IPMatch;
+use crate::error::{binary_error, ASPMatchError};
+use crate::options::{DumpOptions, ParseOptions, SalvageOptions};
+use crate::parser::{
+    check_limit, check_total_bytes, dump_match_as_binary_with_byte_order, dump_to_path_atomically,
+    iprecord_with_options, limited_reader, record_location,
+};
+use nom::error::context;
+use nom::number::complete as number;
+use nom::sequence::pair;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// What a salvage run could recover from a damaged binary match
+#[derive(Debug)]
+pub struct SalvageReport {
+    /// `(size_1, size_2)` announced by the header, `(0, 0)` if the header itself is truncated
+    pub expected: (u64, u64),
+    /// records fully decoded on each side before decoding stopped
+    pub decoded: (u64, u64),
+    /// record pairs in the salvaged match
+    pub recovered_pairs: u64,
+    /// byte offset where decoding stopped, right after the last decoded record
+    pub offset: u64,
+    /// why decoding stopped early, `None` if the match is complete
+    pub reason: Option<ASPMatchError>,
+}
+
+impl SalvageReport {
+    /// Whether every record announced by the header was decoded
+    pub fn is_complete(&self) -> bool {
+        self.reason.is_none()
+    }
+}
+
+/// Decode as many records as possible from a truncated or corrupted binary match
+///
+/// Records are decoded in file order until one fails. A complete match is
+/// returned as is, otherwise the salvaged match only keeps the fully decoded
+/// pairs: image 2 records and as many image 1 records, since image 1 records
+/// are stored first.
+///
+/// ```
+/// use aspmatch::{salvage_binary_match, IPMatch, IPRecord, ParseOptions};
+/// let ipmatch = IPMatch {
+///     image_1: vec![IPRecord::default(); 2],
+///     image_2: vec![IPRecord::default(); 2],
+/// };
+/// let bytes = ipmatch.as_le_bytes();
+/// // cut in the middle of the last record
+/// let (salvaged, report) = salvage_binary_match(&bytes[..bytes.len() - 10], &ParseOptions::default());
+/// assert_eq!(report.expected, (2, 2));
+/// assert_eq!(report.decoded, (2, 1));
+/// assert_eq!(report.recovered_pairs, 1);
+/// assert!(!report.is_complete());
+/// assert_eq!(salvaged.image_1.len(), 1);
+/// assert_eq!(salvaged.image_2.len(), 1);
+/// ```
+pub fn salvage_binary_match(input: &[u8], options: &ParseOptions) -> (IPMatch, SalvageReport) {
+    let e = options.byte_order.endianness();
+    let mut report = SalvageReport {
+        expected: (0, 0),
+        decoded: (0, 0),
+        recovered_pairs: 0,
+        offset: 0,
+        reason: None,
+    };
+    if let Err(e) = check_total_bytes(input, input.len() as u64, &options.limits) {
+        report.reason = Some(binary_error(input, 0, e));
+        return (IPMatch::default(), report);
+    }
+    let header = pair(
+        context("size_1", number::u64(e)),
+        context("size_2", number::u64(e)),
+    )(input);
+    let (mut i, (size_1, size_2)) = match header {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.reason = Some(binary_error(input, 0, e));
+            return (IPMatch::default(), report);
+        }
+    };
+    report.expected = (size_1, size_2);
+    let records = size_1.saturating_add(size_2);
+    if let Err(e) = check_limit(
+        input,
+        "size_1 + size_2",
+        records,
+        options.limits.max_records,
+    ) {
+        report.reason = Some(binary_error(input, 0, e));
+        return (IPMatch::default(), report);
+    }
+    report.offset = (input.len() - i.len()) as u64;
+    let mut ipmatch = IPMatch::default();
+    // no preallocation, the header counts may be corrupted as well
+    for index in 0..records {
+        let (side, record) = record_location(size_1, index);
+        match iprecord_with_options(i, options) {
+            Ok((remaining, ip_record)) => {
+                if index < size_1 {
+                    ipmatch.image_1.push(ip_record);
+                } else {
+                    ipmatch.image_2.push(ip_record);
+                }
+                i = remaining;
+                report.offset = (input.len() - i.len()) as u64;
+            }
+            Err(e) => {
+                let e = e.map(|e| e.with_record(side, record));
+                report.reason = Some(binary_error(input, 0, e));
+                break;
+            }
+        }
+    }
+    report.decoded = (ipmatch.image_1.len() as u64, ipmatch.image_2.len() as u64);
+    if !report.is_complete() {
+        ipmatch.image_1.truncate(ipmatch.image_2.len());
+    }
+    report.recovered_pairs = ipmatch.image_1.len().min(ipmatch.image_2.len()) as u64;
+    (ipmatch, report)
+}
+
+/// Salvage binary match file at path, assuming file fits in RAM
+///
+/// With `options.rewrite`, the salvaged match is written as a consistent
+/// binary match file in the same byte order, possibly over the damaged file.
+pub fn salvage_binary_match_file_path<P: AsRef<Path>>(
+    path: P,
+    options: &SalvageOptions,
+) -> Result<(IPMatch, SalvageReport), ASPMatchError> {
+    let mut buf_reader = limited_reader(BufReader::new(File::open(path)?), &options.limits);
+    let mut buf = vec![];
+    buf_reader.read_to_end(&mut buf)?;
+    let parse_options = ParseOptions {
+        byte_order: options.byte_order,
+        limits: options.limits,
+        ..ParseOptions::default()
+    };
+    let (ipmatch, report) = salvage_binary_match(&buf, &parse_options);
+    if let Some(rewrite) = &options.rewrite {
+        dump_to_path_atomically(rewrite, &DumpOptions::default(), |match_file| {
+            dump_match_as_binary_with_byte_order(&ipmatch, match_file, options.byte_order)
+        })?;
+    }
+    Ok((ipmatch, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::error::ASPMatchError;
+    use crate::options::{ByteOrder, ParseOptions, SalvageOptions};
+    use crate::parser::parse_binary_match_file_path_with_options;
+    use crate::salvage::{salvage_binary_match, salvage_binary_match_file_path};
+
+    fn dummy_ipmatch() -> IPMatch {
+        let record = |x| IPRecord {
+            x,
+            desc: vec![x; 3],
+            ..IPRecord::default()
+        };
+        IPMatch {
+            image_1: (0..3).map(|i| record(i as f32)).collect(),
+            image_2: (3..6).map(|i| record(i as f32)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_salvage_complete() {
+        let ipmatch = dummy_ipmatch();
+        let bytes = ipmatch.as_le_bytes();
+        let (salvaged, report) = salvage_binary_match(&bytes, &ParseOptions::default());
+        assert!(report.is_complete());
+        assert_eq!(report.decoded, (3, 3));
+        assert_eq!(report.recovered_pairs, 3);
+        assert_eq!(report.offset, bytes.len() as u64);
+        assert_eq!(salvaged, ipmatch);
+    }
+
+    #[test]
+    fn test_salvage_truncated() {
+        let ipmatch = dummy_ipmatch();
+        let bytes = ipmatch.as_le_bytes();
+        let record_size = ipmatch.image_1[0].as_le_bytes().len();
+        // every cut point, from inside the header to the last byte
+        for len in 0..bytes.len() {
+            let (salvaged, report) = salvage_binary_match(&bytes[..len], &ParseOptions::default());
+            assert!(!report.is_complete());
+            let records = len.saturating_sub(16) / record_size;
+            let pairs = records.saturating_sub(3);
+            assert_eq!(report.recovered_pairs, pairs as u64);
+            assert_eq!(salvaged.image_1, ipmatch.image_1[..pairs]);
+            assert_eq!(salvaged.image_2, ipmatch.image_2[..pairs]);
+            if len >= 16 {
+                assert_eq!(report.offset, (16 + records * record_size) as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_salvage_reason() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let (_, report) = salvage_binary_match(&bytes[..bytes.len() - 1], &ParseOptions::default());
+        match report.reason {
+            Some(ASPMatchError::BinaryParser { offset, details }) => {
+                // last descriptor value of the last record
+                assert_eq!(offset, report.offset + 45 + 2 * 4);
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(2));
+                assert_eq!(details.field, Some("desc"));
+            }
+            other => panic!("unexpected reason {:?}", other),
+        }
+        let (_, report) = salvage_binary_match(&bytes[..12], &ParseOptions::default());
+        assert_eq!(report.expected, (0, 0));
+        assert!(matches!(
+            report.reason,
+            Some(ASPMatchError::BinaryParser { offset: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_salvage_rewrite() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("match.bin");
+        let bytes = dummy_ipmatch().as_bytes(ByteOrder::BigEndian);
+        std::fs::write(&path, &bytes[..bytes.len() - 20]).unwrap();
+        let options = SalvageOptions {
+            byte_order: ByteOrder::BigEndian,
+            rewrite: Some(path.clone()),
+            ..SalvageOptions::default()
+        };
+        let (salvaged, report) = salvage_binary_match_file_path(&path, &options).unwrap();
+        assert_eq!(report.recovered_pairs, 2);
+        let parse_options = ParseOptions {
+            byte_order: ByteOrder::BigEndian,
+            ..ParseOptions::default()
+        };
+        let rewritten = parse_binary_match_file_path_with_options(&path, &parse_options).unwrap();
+        assert_eq!(rewritten, salvaged);
+    }
+}
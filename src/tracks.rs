@@ -0,0 +1,443 @@
+use crate::data::{IPMatch, IPRecord};
+use crate::error::ASPMatchError;
+use crate::format::parse_match_file_path_auto_with_options;
+use crate::naming::{scan_match_files, MatchFileEntry};
+use crate::options::TrackOptions;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Interest point of a track
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    /// index in [`TrackSet::images`]
+    pub image: usize,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Interest points of several images tied together by matches
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    /// sorted by image, then by coordinates
+    pub observations: Vec<Observation>,
+}
+
+impl Track {
+    /// Number of distinct images observing the track
+    pub fn image_count(&self) -> usize {
+        let mut count = 0;
+        let mut previous = None;
+        for observation in &self.observations {
+            if previous != Some(observation.image) {
+                count += 1;
+                previous = Some(observation.image);
+            }
+        }
+        count
+    }
+
+    /// Whether two interest points of the same image ended up in the track
+    pub fn is_conflicting(&self) -> bool {
+        self.image_count() < self.observations.len()
+    }
+}
+
+/// Tracks built by [`TrackBuilder`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackSet {
+    /// image names, in order of first appearance
+    pub images: Vec<String>,
+    pub tracks: Vec<Track>,
+}
+
+impl TrackSet {
+    /// Tracks observing the same image twice, with their index
+    pub fn conflicting_tracks(&self) -> impl Iterator<Item = (usize, &Track)> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| track.is_conflicting())
+    }
+
+    /// Number of tracks by number of distinct images observing them
+    pub fn image_count_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for track in &self.tracks {
+            *histogram.entry(track.image_count()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Pairwise matches of the tracks by `(image 1, image 2)` indices, image 1 < image 2
+    ///
+    /// Any two observations of distinct images in a track are a pair. Records
+    /// only have `x`/`y`, `xi`/`yi` rounded from them and a `scale` of 1.
+    pub fn pairwise_matches(&self) -> BTreeMap<(usize, usize), IPMatch> {
+        let record = |observation: &Observation| IPRecord {
+            x: observation.x,
+            y: observation.y,
+            xi: observation.x.round() as i32,
+            yi: observation.y.round() as i32,
+            scale: 1.0,
+            ..IPRecord::default()
+        };
+        let mut matches: BTreeMap<(usize, usize), IPMatch> = BTreeMap::new();
+        for track in &self.tracks {
+            for (index, observation_1) in track.observations.iter().enumerate() {
+                for observation_2 in &track.observations[index + 1..] {
+                    let (observation_1, observation_2) =
+                        match observation_1.image.cmp(&observation_2.image) {
+                            Ordering::Less => (observation_1, observation_2),
+                            Ordering::Greater => (observation_2, observation_1),
+                            Ordering::Equal => continue,
+                        };
+                    let ipmatch = matches
+                        .entry((observation_1.image, observation_2.image))
+                        .or_default();
+                    ipmatch.image_1.push(record(observation_1));
+                    ipmatch.image_2.push(record(observation_2));
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// Build multi-image tracks from pairwise matches
+///
+/// Each `(image, interest point)` is a node, interest points of an image
+/// within [`TrackOptions::tolerance`] of an existing node are merged into it
+/// (a node keeps the coordinates it was created with). Matched nodes are
+/// joined with union-find, each connected component is a track.
+///
+/// ```
+/// use aspmatch::{IPMatch, IPRecord, TrackBuilder, TrackOptions};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let point = |x: f32, y: f32| IPRecord { x, y, ..IPRecord::default() };
+/// let mut builder = TrackBuilder::new(TrackOptions::default());
+/// builder.add_match("a", "b", &IPMatch::from_pairs(vec![(point(1.0, 2.0), point(3.0, 4.0))]))?;
+/// builder.add_match("b", "c", &IPMatch::from_pairs(vec![(point(3.1, 4.0), point(5.0, 6.0))]))?;
+/// let track_set = builder.build();
+/// assert_eq!(track_set.images, vec!["a", "b", "c"]);
+/// assert_eq!(track_set.tracks.len(), 1);
+/// assert_eq!(track_set.tracks[0].image_count(), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TrackBuilder {
+    options: TrackOptions,
+    images: Vec<String>,
+    image_indices: HashMap<String, usize>,
+    nodes: Vec<Observation>,
+    /// node indices by `(image, cell x, cell y)`, cells are `tolerance` wide
+    grid: HashMap<(usize, i64, i64), Vec<usize>>,
+    /// union-find parent of each node
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl TrackBuilder {
+    pub fn new(options: TrackOptions) -> Self {
+        TrackBuilder {
+            options,
+            images: vec![],
+            image_indices: HashMap::new(),
+            nodes: vec![],
+            grid: HashMap::new(),
+            parents: vec![],
+            sizes: vec![],
+        }
+    }
+
+    /// Tie the interest points matched between `image_1` and `image_2`
+    ///
+    /// Fails with [`ASPMatchError::UnequalSides`] if `ipmatch` sides differ in length.
+    pub fn add_match(
+        &mut self,
+        image_1: &str,
+        image_2: &str,
+        ipmatch: &IPMatch,
+    ) -> Result<(), ASPMatchError> {
+        let image_1 = self.image_index(image_1);
+        let image_2 = self.image_index(image_2);
+        for (record_1, record_2) in ipmatch.pairs()? {
+            let node_1 = self.node(image_1, record_1.x, record_1.y);
+            let node_2 = self.node(image_2, record_2.x, record_2.y);
+            self.union(node_1, node_2);
+        }
+        Ok(())
+    }
+
+    /// Add the matches of a directory scanned with [`scan_match_files`]
+    ///
+    /// Images are identified by their stem. With
+    /// [`TrackOptions::prefer_clean`], the `-clean.match` file of an image pair
+    /// is used instead of the unfiltered one when both exist, otherwise only
+    /// unfiltered files are used.
+    pub fn add_directory<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        prefix: Option<&str>,
+    ) -> Result<(), ASPMatchError> {
+        let mut pairs: BTreeMap<(String, String), MatchFileEntry> = BTreeMap::new();
+        for entry in scan_match_files(directory, prefix)? {
+            if entry.clean && !self.options.prefer_clean {
+                continue;
+            }
+            let key = (entry.image_1.clone(), entry.image_2.clone());
+            let replace = match pairs.get(&key) {
+                Some(previous) => entry.clean && !previous.clean,
+                None => true,
+            };
+            if replace {
+                pairs.insert(key, entry);
+            }
+        }
+        for ((image_1, image_2), entry) in pairs {
+            let (_, ipmatch) =
+                parse_match_file_path_auto_with_options(&entry.path, &self.options.parse)?;
+            self.add_match(&image_1, &image_2, &ipmatch)?;
+        }
+        Ok(())
+    }
+
+    /// Group the nodes into tracks, in order of first appearance
+    pub fn build(mut self) -> TrackSet {
+        let mut track_indices: HashMap<usize, usize> = HashMap::new();
+        let mut tracks: Vec<Track> = vec![];
+        for node in 0..self.nodes.len() {
+            let root = self.find(node);
+            let track = *track_indices.entry(root).or_insert_with(|| {
+                tracks.push(Track::default());
+                tracks.len() - 1
+            });
+            tracks[track].observations.push(self.nodes[node].clone());
+        }
+        for track in &mut tracks {
+            track.observations.sort_by(|a, b| {
+                a.image
+                    .cmp(&b.image)
+                    .then(a.x.total_cmp(&b.x))
+                    .then(a.y.total_cmp(&b.y))
+            });
+        }
+        TrackSet {
+            images: self.images,
+            tracks,
+        }
+    }
+
+    fn image_index(&mut self, image: &str) -> usize {
+        if let Some(index) = self.image_indices.get(image) {
+            return *index;
+        }
+        self.images.push(image.to_string());
+        self.image_indices
+            .insert(image.to_string(), self.images.len() - 1);
+        self.images.len() - 1
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i64, i64) {
+        let size = if self.options.tolerance > 0.0 {
+            self.options.tolerance
+        } else {
+            1.0
+        };
+        // saturating casts, infinite coordinates land in the extreme cells
+        ((x / size).floor() as i64, (y / size).floor() as i64)
+    }
+
+    /// Node of the interest point, created if no node is within tolerance
+    fn node(&mut self, image: usize, x: f32, y: f32) -> usize {
+        let (cell_x, cell_y) = self.cell(x, y);
+        let tolerance_squared = self.options.tolerance * self.options.tolerance;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let cell = (image, cell_x.saturating_add(dx), cell_y.saturating_add(dy));
+                let found = self.grid.get(&cell).and_then(|nodes| {
+                    nodes.iter().copied().find(|node| {
+                        let node = &self.nodes[*node];
+                        let (node_x, node_y) = (node.x - x, node.y - y);
+                        node_x * node_x + node_y * node_y <= tolerance_squared
+                    })
+                });
+                if let Some(node) = found {
+                    return node;
+                }
+            }
+        }
+        let node = self.nodes.len();
+        self.nodes.push(Observation { image, x, y });
+        self.parents.push(node);
+        self.sizes.push(1);
+        self.grid
+            .entry((image, cell_x, cell_y))
+            .or_default()
+            .push(node);
+        node
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parents[node] != node {
+            // path halving
+            self.parents[node] = self.parents[self.parents[node]];
+            node = self.parents[node];
+        }
+        node
+    }
+
+    fn union(&mut self, node_1: usize, node_2: usize) {
+        let (root_1, root_2) = (self.find(node_1), self.find(node_2));
+        if root_1 == root_2 {
+            return;
+        }
+        let (large, small) = if self.sizes[root_1] >= self.sizes[root_2] {
+            (root_1, root_2)
+        } else {
+            (root_2, root_1)
+        };
+        self.parents[small] = large;
+        self.sizes[large] += self.sizes[small];
+    }
+}
+
+/// Build the tracks of the match files of `directory`, see [`TrackBuilder::add_directory`]
+pub fn build_tracks_from_directory<P: AsRef<Path>>(
+    directory: P,
+    prefix: Option<&str>,
+    options: &TrackOptions,
+) -> Result<TrackSet, ASPMatchError> {
+    let mut builder = TrackBuilder::new(options.clone());
+    builder.add_directory(directory, prefix)?;
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord};
+    use crate::error::ASPMatchError;
+    use crate::naming::{clean_match_file_path, match_file_path};
+    use crate::options::TrackOptions;
+    use crate::parser::dump_match_as_binary_to_path;
+    use crate::tracks::{build_tracks_from_directory, TrackBuilder};
+    use std::collections::BTreeMap;
+
+    fn point(x: f32, y: f32) -> IPRecord {
+        IPRecord {
+            x,
+            y,
+            ..IPRecord::default()
+        }
+    }
+
+    /// `[x1, y1, x2, y2]` pairs
+    fn ipmatch(pairs: &[[f32; 4]]) -> IPMatch {
+        pairs
+            .iter()
+            .map(|[x1, y1, x2, y2]| (point(*x1, *y1), point(*x2, *y2)))
+            .collect()
+    }
+
+    #[test]
+    fn test_tracks() {
+        let mut builder = TrackBuilder::new(TrackOptions::default());
+        let ab = ipmatch(&[[0.0, 0.0, 10.0, 10.0], [50.0, 50.0, 60.0, 60.0]]);
+        // (10.3, 9.8) is within tolerance of (10, 10), (1.0, 0.0) is not of (0, 0)
+        let bc = ipmatch(&[[10.3, 9.8, 20.0, 20.0], [100.0, 0.0, 200.0, 0.0]]);
+        let ca = ipmatch(&[[20.0, 20.0, 1.0, 0.0]]);
+        builder.add_match("a", "b", &ab).unwrap();
+        builder.add_match("b", "c", &bc).unwrap();
+        builder.add_match("c", "a", &ca).unwrap();
+        let track_set = builder.build();
+        assert_eq!(track_set.images, vec!["a", "b", "c"]);
+        assert_eq!(track_set.tracks.len(), 3);
+        let track = &track_set.tracks[0];
+        let observations: Vec<(usize, f32, f32)> = track
+            .observations
+            .iter()
+            .map(|o| (o.image, o.x, o.y))
+            .collect();
+        assert_eq!(
+            observations,
+            vec![
+                (0, 0.0, 0.0),
+                (0, 1.0, 0.0),
+                (1, 10.0, 10.0),
+                (2, 20.0, 20.0)
+            ]
+        );
+        assert_eq!(track.image_count(), 3);
+        let conflicting: Vec<usize> = track_set.conflicting_tracks().map(|(i, _)| i).collect();
+        assert_eq!(conflicting, vec![0]);
+        assert_eq!(
+            track_set.image_count_histogram(),
+            BTreeMap::from([(2, 2), (3, 1)])
+        );
+    }
+
+    #[test]
+    fn test_exact_tolerance() {
+        let mut builder = TrackBuilder::new(TrackOptions {
+            tolerance: 0.0,
+            ..TrackOptions::default()
+        });
+        let ab = ipmatch(&[[0.5, 0.5, 1.0, 1.0], [0.5, 0.6, 2.0, 2.0]]);
+        builder.add_match("a", "b", &ab).unwrap();
+        builder.add_match("a", "b", &ab).unwrap();
+        assert_eq!(builder.build().tracks.len(), 2);
+        let mut unequal = ab;
+        unequal.image_2.pop();
+        let mut builder = TrackBuilder::new(TrackOptions::default());
+        assert!(matches!(
+            builder.add_match("a", "b", &unequal),
+            Err(ASPMatchError::UnequalSides { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extreme_coordinates() {
+        let mut builder = TrackBuilder::new(TrackOptions::default());
+        let ab = ipmatch(&[
+            [f32::INFINITY, 0.0, 1.0, 1.0],
+            [1e30, f32::NEG_INFINITY, 2.0, 2.0],
+            [f32::MAX, -1e30, 3.0, 3.0],
+            [f32::NAN, 0.0, 4.0, 4.0],
+        ]);
+        builder.add_match("a", "b", &ab).unwrap();
+        builder.add_match("a", "b", &ab).unwrap();
+        // finite points are merged again, infinite and NaN ones never match a node
+        let track_set = builder.build();
+        assert_eq!(track_set.tracks.len(), 4);
+        assert_eq!(track_set.image_count_histogram(), BTreeMap::from([(2, 4)]));
+    }
+
+    #[test]
+    fn test_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let prefix = directory.path().join("run");
+        let prefix = prefix.to_str().unwrap();
+        let ab = ipmatch(&[[0.0, 0.0, 1.0, 1.0], [5.0, 5.0, 6.0, 6.0]]);
+        let ab_clean = ipmatch(&[[0.0, 0.0, 1.0, 1.0]]);
+        let bc = ipmatch(&[[1.0, 1.0, 2.0, 2.0]]);
+        dump_match_as_binary_to_path(&ab, match_file_path(prefix, "a.tif", "b.tif")).unwrap();
+        dump_match_as_binary_to_path(&ab_clean, clean_match_file_path(prefix, "a.tif", "b.tif"))
+            .unwrap();
+        dump_match_as_binary_to_path(&bc, match_file_path(prefix, "b.tif", "c.tif")).unwrap();
+        let options = TrackOptions::default();
+        let track_set = build_tracks_from_directory(directory.path(), None, &options).unwrap();
+        assert_eq!(track_set.images, vec!["a", "b", "c"]);
+        assert_eq!(track_set.image_count_histogram(), BTreeMap::from([(3, 1)]));
+        let options = TrackOptions {
+            prefer_clean: false,
+            ..TrackOptions::default()
+        };
+        let track_set = build_tracks_from_directory(directory.path(), None, &options).unwrap();
+        assert_eq!(
+            track_set.image_count_histogram(),
+            BTreeMap::from([(2, 1), (3, 1)])
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use crate::data::{IPMatch, IPRecord, MatchSide};
+use crate::options::ValidationOptions;
+use std::fmt;
+
+/// How serious a validation finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// suspicious, ASP tools accept the match anyway
+    Warning,
+    /// the match is not usable as an ASP match
+    Error,
+}
+
+/// What a validation finding is about
+#[derive(Clone, Debug, PartialEq)]
+pub enum IssueKind {
+    /// `image_1` and `image_2` lengths differ, ASP pairs records by index
+    UnequalSides { size_1: usize, size_2: usize },
+    /// descriptor length differs from the first record one
+    DescLength { expected: usize, found: usize },
+    /// `x` or `y` is NaN or infinite
+    NonFiniteCoordinate { field: &'static str, value: f32 },
+    /// `xi` or `yi` is too far from `x` or `y`
+    IntegerCoordinate {
+        field: &'static str,
+        value: f32,
+        integer: i32,
+    },
+    /// `x` or `y` is negative, outside of the image
+    NegativeCoordinate { field: &'static str, value: f32 },
+}
+
+/// One validation finding, see [`IPMatch::validate`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// image side of the record, `None` for match wide findings
+    pub side: Option<MatchSide>,
+    /// index of the record within its image side
+    pub record: Option<usize>,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: ")?,
+            Severity::Error => write!(f, "error: ")?,
+        }
+        match (self.side, self.record) {
+            (Some(MatchSide::Image1), Some(record)) => write!(f, "image 1 record {}, ", record)?,
+            (Some(MatchSide::Image2), Some(record)) => write!(f, "image 2 record {}, ", record)?,
+            _ => {}
+        }
+        match &self.kind {
+            IssueKind::UnequalSides { size_1, size_2 } => write!(
+                f,
+                "{} image 1 records but {} image 2 records",
+                size_1, size_2
+            ),
+            IssueKind::DescLength { expected, found } => {
+                write!(f, "{} descriptor values, expected {}", found, expected)
+            }
+            IssueKind::NonFiniteCoordinate { field, value } => {
+                write!(f, "`{}` is not finite ({})", field, value)
+            }
+            IssueKind::IntegerCoordinate {
+                field,
+                value,
+                integer,
+            } => write!(
+                f,
+                "`{}i` {} does not match `{}` {}",
+                field, integer, field, value
+            ),
+            IssueKind::NegativeCoordinate { field, value } => {
+                write!(f, "`{}` is negative ({})", field, value)
+            }
+        }
+    }
+}
+
+impl IPMatch {
+    /// Check the match is usable by ASP tools, e.g. before `bundle_adjust`
+    ///
+    /// Returns every finding, match wide ones first then record by record,
+    /// an empty `Vec` if the match is valid.
+    ///
+    /// ```
+    /// use aspmatch::{IPMatch, IPRecord, Severity, ValidationOptions};
+    /// let record = IPRecord { x: 10.4, y: 3.0, xi: 10, yi: 3, ..IPRecord::default() };
+    /// let mut ipmatch = IPMatch { image_1: vec![record.clone()], image_2: vec![record] };
+    /// assert!(ipmatch.validate(&ValidationOptions::default()).is_empty());
+    /// ipmatch.image_2[0].y = f32::NAN;
+    /// let issues = ipmatch.validate(&ValidationOptions::default());
+    /// assert_eq!(issues.len(), 1);
+    /// assert_eq!(issues[0].severity, Severity::Error);
+    /// assert_eq!(issues[0].to_string(), "error: image 2 record 0, `y` is not finite (NaN)");
+    /// ```
+    pub fn validate(&self, options: &ValidationOptions) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+        if self.image_1.len() != self.image_2.len() {
+            issues.push(ValidationIssue {
+                severity: Severity::Error,
+                side: None,
+                record: None,
+                kind: IssueKind::UnequalSides {
+                    size_1: self.image_1.len(),
+                    size_2: self.image_2.len(),
+                },
+            });
+        }
+        let desc_len = self
+            .image_1
+            .first()
+            .or_else(|| self.image_2.first())
+            .map(|record| record.desc.len());
+        let sides = [
+            (MatchSide::Image1, &self.image_1),
+            (MatchSide::Image2, &self.image_2),
+        ];
+        for (side, records) in sides {
+            for (index, record) in records.iter().enumerate() {
+                validate_record(record, desc_len, options, &mut |severity, kind| {
+                    issues.push(ValidationIssue {
+                        severity,
+                        side: Some(side),
+                        record: Some(index),
+                        kind,
+                    })
+                });
+            }
+        }
+        issues
+    }
+}
+
+fn validate_record<F: FnMut(Severity, IssueKind)>(
+    record: &IPRecord,
+    desc_len: Option<usize>,
+    options: &ValidationOptions,
+    report: &mut F,
+) {
+    let soft = if options.strict {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+    if let Some(expected) = desc_len.filter(|expected| *expected != record.desc.len()) {
+        let found = record.desc.len();
+        report(Severity::Error, IssueKind::DescLength { expected, found });
+    }
+    for (field, value, integer) in [("x", record.x, record.xi), ("y", record.y, record.yi)] {
+        if !value.is_finite() {
+            report(
+                Severity::Error,
+                IssueKind::NonFiniteCoordinate { field, value },
+            );
+            continue;
+        }
+        if options.check_integer_coordinates
+            && (integer as f64 - value as f64).abs() >= options.integer_tolerance as f64
+        {
+            let kind = IssueKind::IntegerCoordinate {
+                field,
+                value,
+                integer,
+            };
+            report(soft, kind);
+        }
+        if options.check_negative_coordinates && value < 0.0 {
+            report(soft, IssueKind::NegativeCoordinate { field, value });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::options::ValidationOptions;
+    use crate::validate::{IssueKind, Severity, ValidationIssue};
+
+    fn record(x: f32, y: f32, ndesc: usize) -> IPRecord {
+        IPRecord {
+            x,
+            y,
+            xi: x.round() as i32,
+            yi: y.round() as i32,
+            desc: vec![0.5; ndesc],
+            ..IPRecord::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_valid() {
+        let ipmatch = IPMatch {
+            image_1: vec![record(0.0, 1.5, 4), record(10.9, 2.0, 4)],
+            image_2: vec![record(3.2, 4.7, 4), record(5.0, 6.0, 4)],
+        };
+        assert!(ipmatch.validate(&ValidationOptions::default()).is_empty());
+        assert!(IPMatch::default()
+            .validate(&ValidationOptions::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_issues() {
+        let mut shifted = record(10.9, 2.0, 4);
+        shifted.xi = 9; // neither rounded nor truncated
+        let ipmatch = IPMatch {
+            image_1: vec![record(1.0, 1.0, 4), record(2.0, 2.0, 3), shifted],
+            image_2: vec![record(f32::INFINITY, -1.0, 4), record(3.0, 3.0, 4)],
+        };
+        let issues = ipmatch.validate(&ValidationOptions::default());
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.side, issue.record))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, None, None),
+                (Severity::Error, Some(MatchSide::Image1), Some(1)),
+                (Severity::Warning, Some(MatchSide::Image1), Some(2)),
+                (Severity::Error, Some(MatchSide::Image2), Some(0)),
+                (Severity::Warning, Some(MatchSide::Image2), Some(0)),
+            ]
+        );
+        assert_eq!(
+            issues[0].kind,
+            IssueKind::UnequalSides {
+                size_1: 3,
+                size_2: 2
+            }
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "warning: image 1 record 2, `xi` 9 does not match `x` 10.9"
+        );
+        assert_eq!(
+            issues[4],
+            ValidationIssue {
+                severity: Severity::Warning,
+                side: Some(MatchSide::Image2),
+                record: Some(0),
+                kind: IssueKind::NegativeCoordinate {
+                    field: "y",
+                    value: -1.0
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_options() {
+        let ipmatch = IPMatch {
+            image_1: vec![record(-2.0, 1.0, 0)],
+            image_2: vec![record(1.0, 1.0, 0)],
+        };
+        let strict = ValidationOptions {
+            strict: true,
+            ..ValidationOptions::default()
+        };
+        let issues = ipmatch.validate(&strict);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        let lax = ValidationOptions {
+            check_negative_coordinates: false,
+            ..ValidationOptions::default()
+        };
+        assert!(ipmatch.validate(&lax).is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::data::{IPRecord, MatchSide, MINIMUM_RECORD_SIZE_IN_BYTES};
+use crate::error::{binary_error, ASPMatchError, MatchParseError};
+use crate::options::{ByteOrder, ParseOptions};
+use crate::parser::{check_limit, check_total_bytes, checked_record_count};
+use memmap2::Mmap;
+use nom::error::ErrorKind;
+use std::fs::File;
+use std::path::Path;
+
+const HEADER_SIZE_IN_BYTES: usize = 2 * 8;
+const NDESC_OFFSET: usize = MINIMUM_RECORD_SIZE_IN_BYTES as usize - 8;
+
+/// Borrowed view over a binary match, records are decoded on access.
+///
+/// Only the start offset of every record is stored, no `IPRecord` is
+/// allocated unless [`RecordView::to_iprecord`] is called.
+///
+/// Use [`MatchFileView::open`] to memory-map a match file or
+/// [`MatchFileView::from_bytes`] for bytes already in memory. Both expect
+/// little endian files, the `_with_options` variants decode
+/// [`ParseOptions::byte_order`].
+///
+/// ```
+/// use aspmatch::{IPMatch, IPRecord, MatchFileView, MatchSide};
+/// # use aspmatch::ASPMatchError;
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let record = IPRecord { x: 1.5, desc: vec![0.5, 0.25], ..IPRecord::default() };
+/// let ipmatch = IPMatch { image_1: vec![record.clone()], image_2: vec![record] };
+/// let view = MatchFileView::from_bytes(ipmatch.as_le_bytes())?;
+/// let first = view.record(MatchSide::Image2, 0).unwrap();
+/// assert_eq!(first.x(), 1.5);
+/// assert_eq!(first.desc().to_vec(), vec![0.5, 0.25]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MatchFileView<B = Mmap> {
+    bytes: B,
+    image_1: Vec<usize>,
+    image_2: Vec<usize>,
+    byte_order: ByteOrder,
+}
+
+impl MatchFileView<Mmap> {
+    /// Memory-map binary match file at path and index its records
+    ///
+    /// The file must not be modified while the view is alive.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ASPMatchError> {
+        MatchFileView::open_with_options(path, &ParseOptions::default())
+    }
+
+    /// Memory-map binary match file at path and index its records with options
+    ///
+    /// Only `byte_order` and `limits` apply. The file must not be modified
+    /// while the view is alive.
+    pub fn open_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<Self, ASPMatchError> {
+        let match_file = File::open(path)?;
+        // SAFETY: the mapping is read-only, modifying the underlying file
+        // while it is mapped is documented as forbidden above.
+        let mmap = unsafe { Mmap::map(&match_file)? };
+        MatchFileView::from_bytes_with_options(mmap, options)
+    }
+}
+
+impl<B: AsRef<[u8]>> MatchFileView<B> {
+    /// Index records of a binary match already in memory
+    pub fn from_bytes(bytes: B) -> Result<Self, ASPMatchError> {
+        MatchFileView::from_bytes_with_options(bytes, &ParseOptions::default())
+    }
+
+    /// Index records of a binary match already in memory with options
+    ///
+    /// Only `byte_order` and `limits` apply.
+    pub fn from_bytes_with_options(
+        bytes: B,
+        options: &ParseOptions,
+    ) -> Result<Self, ASPMatchError> {
+        let (image_1, image_2) = index_records(bytes.as_ref(), options)?;
+        Ok(MatchFileView {
+            bytes,
+            image_1,
+            image_2,
+            byte_order: options.byte_order,
+        })
+    }
+
+    /// Byte order records are decoded in
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// Number of records for image `side`
+    pub fn len(&self, side: MatchSide) -> usize {
+        self.offsets(side).len()
+    }
+
+    /// `true` if the match holds no record
+    pub fn is_empty(&self) -> bool {
+        self.image_1.is_empty() && self.image_2.is_empty()
+    }
+
+    /// Record `index` of image `side`, `None` if out of bounds
+    pub fn record(&self, side: MatchSide, index: usize) -> Option<RecordView<'_>> {
+        let offset = *self.offsets(side).get(index)?;
+        Some(RecordView::new(
+            &self.bytes.as_ref()[offset..],
+            self.byte_order,
+        ))
+    }
+
+    /// Iterate over records of image `side`
+    pub fn records(&self, side: MatchSide) -> impl Iterator<Item = RecordView<'_>> + '_ {
+        let bytes = self.bytes.as_ref();
+        let byte_order = self.byte_order;
+        self.offsets(side)
+            .iter()
+            .map(move |offset| RecordView::new(&bytes[*offset..], byte_order))
+    }
+
+    fn offsets(&self, side: MatchSide) -> &[usize] {
+        match side {
+            MatchSide::Image1 => &self.image_1,
+            MatchSide::Image2 => &self.image_2,
+        }
+    }
+}
+
+/// Start offsets of image 1 and image 2 records, checking every record fits
+fn index_records(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<(Vec<usize>, Vec<usize>), ASPMatchError> {
+    let limits = &options.limits;
+    let byte_order = options.byte_order;
+    check_total_bytes(bytes, bytes.len() as u64, limits).map_err(|e| binary_error(bytes, 0, e))?;
+    let eof = |offset: usize, field: &'static str| {
+        nom::Err::Error(MatchParseError::new(&bytes[offset..], ErrorKind::Eof).with_field(field))
+    };
+    if bytes.len() < HEADER_SIZE_IN_BYTES {
+        return Err(binary_error(bytes, 0, eof(bytes.len(), "size_1")));
+    }
+    let size_1 = byte_order.u64_at(bytes, 0);
+    let size_2 = byte_order.u64_at(bytes, 8);
+    check_limit(
+        bytes,
+        "size_1 + size_2",
+        size_1.saturating_add(size_2),
+        limits.max_records,
+    )
+    .map_err(|e| binary_error(bytes, 0, e))?;
+    if checked_record_count(size_1, size_2, bytes.len()).is_none() {
+        let max_records = bytes.len() / MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+        let e = MatchParseError::new(bytes, ErrorKind::Verify)
+            .with_field("size_1 + size_2")
+            .with_expected_found(
+                format!("at most {} records", max_records),
+                format!("{} + {} records", size_1, size_2),
+            );
+        return Err(binary_error(bytes, 0, nom::Err::Error(e)));
+    }
+    let mut offset = HEADER_SIZE_IN_BYTES;
+    let mut index_side = |side: MatchSide, size: u64| {
+        let mut offsets = Vec::with_capacity(size as usize);
+        for record in 0..size {
+            let desc_offset = offset + MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+            if desc_offset > bytes.len() {
+                let e = eof(bytes.len(), "ndesc").map(|e| e.with_record(side, record));
+                return Err(binary_error(bytes, 0, e));
+            }
+            let ndesc = byte_order.u64_at(bytes, offset + NDESC_OFFSET);
+            check_limit(&bytes[desc_offset..], "ndesc", ndesc, limits.max_ndesc).map_err(|e| {
+                let e = e.map(|e| e.with_record(side, record));
+                binary_error(bytes, 0, e)
+            })?;
+            let end = usize::try_from(ndesc)
+                .ok()
+                .and_then(|ndesc| ndesc.checked_mul(4))
+                .and_then(|desc_size| desc_offset.checked_add(desc_size))
+                .filter(|end| *end <= bytes.len());
+            match end {
+                Some(end) => {
+                    offsets.push(offset);
+                    offset = end;
+                }
+                None => {
+                    let e = eof(desc_offset, "desc").map(|e| e.with_record(side, record));
+                    return Err(binary_error(bytes, 0, e));
+                }
+            }
+        }
+        Ok(offsets)
+    };
+    let image_1 = index_side(MatchSide::Image1, size_1)?;
+    let image_2 = index_side(MatchSide::Image2, size_2)?;
+    Ok((image_1, image_2))
+}
+
+/// Borrowed view over one binary record, fields are decoded on access
+#[derive(Clone, Copy, Debug)]
+pub struct RecordView<'a> {
+    bytes: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+impl<'a> RecordView<'a> {
+    fn new(bytes: &'a [u8], byte_order: ByteOrder) -> Self {
+        RecordView { bytes, byte_order }
+    }
+
+    fn f32_at(&self, offset: usize) -> f32 {
+        self.byte_order.f32_at(self.bytes, offset)
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        self.byte_order.u32_at(self.bytes, offset)
+    }
+
+    fn i32_at(&self, offset: usize) -> i32 {
+        self.byte_order.i32_at(self.bytes, offset)
+    }
+
+    pub fn x(&self) -> f32 {
+        self.f32_at(0)
+    }
+
+    pub fn y(&self) -> f32 {
+        self.f32_at(4)
+    }
+
+    pub fn xi(&self) -> i32 {
+        self.i32_at(8)
+    }
+
+    pub fn yi(&self) -> i32 {
+        self.i32_at(12)
+    }
+
+    pub fn orientation(&self) -> f32 {
+        self.f32_at(16)
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.f32_at(20)
+    }
+
+    pub fn interest(&self) -> f32 {
+        self.f32_at(24)
+    }
+
+    pub fn polarity(&self) -> u8 {
+        self.bytes[28]
+    }
+
+    pub fn octave(&self) -> u32 {
+        self.u32_at(29)
+    }
+
+    pub fn scale_lvl(&self) -> u32 {
+        self.u32_at(33)
+    }
+
+    /// Number of descriptor values
+    pub fn ndesc(&self) -> usize {
+        self.byte_order.u64_at(self.bytes, NDESC_OFFSET) as usize
+    }
+
+    /// Descriptor values
+    ///
+    /// Records are packed, descriptor values are usually not aligned for
+    /// `f32` hence a view decoding them on access rather than a `&[f32]`.
+    pub fn desc(&self) -> DescView<'a> {
+        let start = MINIMUM_RECORD_SIZE_IN_BYTES as usize;
+        DescView {
+            bytes: &self.bytes[start..start + 4 * self.ndesc()],
+            byte_order: self.byte_order,
+        }
+    }
+
+    /// Decode the whole record
+    pub fn to_iprecord(&self) -> IPRecord {
+        IPRecord {
+            x: self.x(),
+            y: self.y(),
+            xi: self.xi(),
+            yi: self.yi(),
+            orientation: self.orientation(),
+            scale: self.scale(),
+            interest: self.interest(),
+            polarity: self.polarity(),
+            octave: self.octave(),
+            scale_lvl: self.scale_lvl(),
+            desc: self.desc().to_vec(),
+        }
+    }
+}
+
+/// Borrowed view over descriptor values of a record
+#[derive(Clone, Copy, Debug)]
+pub struct DescView<'a> {
+    bytes: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+impl<'a> DescView<'a> {
+    pub fn len(&self) -> usize {
+        self.bytes.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Descriptor value at `index`, `None` if out of bounds
+    pub fn get(&self, index: usize) -> Option<f32> {
+        (index < self.len()).then(|| self.byte_order.f32_at(self.bytes, 4 * index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + 'a {
+        let byte_order = self.byte_order;
+        self.bytes
+            .chunks_exact(4)
+            .map(move |chunk| byte_order.f32_at(chunk, 0))
+    }
+
+    /// Raw bytes of the descriptor values, in [`MatchFileView::byte_order`]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn to_vec(&self) -> Vec<f32> {
+        self.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{IPMatch, IPRecord, MatchSide};
+    use crate::view::MatchFileView;
+    use crate::{
+        dump_match_as_binary_to_file, ASPMatchError, ByteOrder, ParseLimits, ParseOptions,
+    };
+    use std::io::Write;
+
+    fn dummy_ipmatch() -> IPMatch {
+        let record = |x: f32, ndesc: usize| IPRecord {
+            x,
+            y: -x,
+            xi: x as i32,
+            yi: -x as i32,
+            orientation: 0.5,
+            scale: 2.0,
+            interest: 0.25,
+            polarity: 1,
+            octave: 3,
+            scale_lvl: 4,
+            desc: (0..ndesc).map(|d| x + d as f32).collect(),
+        };
+        IPMatch {
+            image_1: vec![record(1.0, 3), record(2.0, 0)],
+            image_2: vec![record(3.0, 1), record(4.0, 5)],
+        }
+    }
+
+    #[test]
+    fn test_match_file_view() {
+        let expected = dummy_ipmatch();
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        dump_match_as_binary_to_file(&expected, tmpfile.as_file_mut()).unwrap();
+        tmpfile.flush().unwrap();
+        let view = MatchFileView::open(tmpfile.path()).unwrap();
+        assert_eq!(view.len(MatchSide::Image1), 2);
+        assert_eq!(view.len(MatchSide::Image2), 2);
+        let image_1: Vec<IPRecord> = view
+            .records(MatchSide::Image1)
+            .map(|r| r.to_iprecord())
+            .collect();
+        let image_2: Vec<IPRecord> = view
+            .records(MatchSide::Image2)
+            .map(|r| r.to_iprecord())
+            .collect();
+        assert_eq!(IPMatch { image_1, image_2 }, expected);
+        let record = view.record(MatchSide::Image2, 1).unwrap();
+        assert_eq!((record.x(), record.yi()), (4.0, -4));
+        assert_eq!(record.desc().get(4), Some(8.0));
+        assert_eq!(record.desc().get(5), None);
+        assert!(view.record(MatchSide::Image2, 2).is_none());
+    }
+
+    #[test]
+    fn test_match_file_view_truncated() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let truncated = &bytes[..bytes.len() - 1];
+        match MatchFileView::from_bytes(truncated) {
+            Err(ASPMatchError::BinaryParser { details, .. }) => {
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("desc"));
+            }
+            _ => panic!("truncated match should not be indexed"),
+        }
+    }
+
+    #[test]
+    fn test_match_file_view_limits() {
+        let bytes = dummy_ipmatch().as_le_bytes();
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_ndesc: 4,
+                ..ParseLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        match MatchFileView::from_bytes_with_options(bytes.as_slice(), &options) {
+            Err(ASPMatchError::BinaryParser { details, .. }) => {
+                assert_eq!(details.side, Some(MatchSide::Image2));
+                assert_eq!(details.record, Some(1));
+                assert_eq!(details.field, Some("ndesc"));
+            }
+            _ => panic!("ndesc above limit should not be indexed"),
+        }
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_total_bytes: bytes.len() as u64 - 1,
+                ..ParseLimits::default()
+            },
+            ..ParseOptions::default()
+        };
+        assert!(MatchFileView::from_bytes_with_options(bytes.as_slice(), &options).is_err());
+    }
+
+    #[test]
+    fn test_match_file_view_big_endian() {
+        let expected = dummy_ipmatch();
+        let bytes = expected.as_bytes(ByteOrder::BigEndian);
+        // the counts do not fit the content read as little endian
+        assert!(MatchFileView::from_bytes(bytes.as_slice()).is_err());
+        let options = ParseOptions {
+            byte_order: ByteOrder::BigEndian,
+            ..ParseOptions::default()
+        };
+        let view = MatchFileView::from_bytes_with_options(bytes.as_slice(), &options).unwrap();
+        assert_eq!(view.byte_order(), ByteOrder::BigEndian);
+        let image_2: Vec<IPRecord> = view
+            .records(MatchSide::Image2)
+            .map(|r| r.to_iprecord())
+            .collect();
+        assert_eq!(image_2, expected.image_2);
+        let record = view.record(MatchSide::Image1, 0).unwrap();
+        assert_eq!((record.octave(), record.scale_lvl()), (3, 4));
+        assert_eq!(record.desc().get(2), Some(3.0));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::data::IPRecord;
+use crate::error::ASPMatchError;
+use crate::options::ByteOrder;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+/// Rewrite header at given position with given pair count
+type PatchHeader<W> = fn(&mut W, u64, u64, ByteOrder) -> io::Result<()>;
+
+/// Where image 2 records wait until all image 1 records are written
+enum Spill {
+    TempFile(BufWriter<File>),
+    Memory(Vec<u8>),
+}
+
+/// Incremental binary match writer.
+///
+/// The binary layout stores every image 1 record before every image 2 record,
+/// so pairs are written as follows: image 1 records go straight to the
+/// underlying writer, image 2 records are set aside until [`MatchWriter::finish`].
+///
+/// * [`MatchWriter::new`] (seekable writer): image 2 records are spilled to an
+///   anonymous temporary file, then the header is rewritten on `finish()`.
+/// * [`MatchWriter::with_pair_count`] (any writer): the header is written
+///   upfront from the declared pair count and image 2 records are buffered in
+///   memory.
+///
+/// Both write little endian files, [`MatchWriter::with_byte_order`] and
+/// [`MatchWriter::with_pair_count_and_byte_order`] write records and header
+/// counts in a given byte order.
+///
+/// ```
+/// use aspmatch::{parse_binary_match_file, IPRecord, MatchWriter};
+/// # use aspmatch::ASPMatchError;
+/// # use std::io::{Seek, SeekFrom};
+/// # fn main() -> Result<(), ASPMatchError> {
+/// let tmpfile = tempfile::tempfile().unwrap();
+/// let mut writer = MatchWriter::new(tmpfile)?;
+/// for _ in 0..3 {
+///     writer.write_pair(&IPRecord::default(), &IPRecord::default())?;
+/// }
+/// let mut tmpfile = writer.finish()?;
+/// tmpfile.seek(SeekFrom::Start(0)).unwrap();
+/// let parsed = parse_binary_match_file(&tmpfile)?;
+/// assert_eq!(parsed.image_1.len(), 3);
+/// assert_eq!(parsed.image_2.len(), 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MatchWriter<W: Write> {
+    writer: BufWriter<W>,
+    header_position: u64,
+    pairs: u64,
+    declared_pairs: Option<u64>,
+    spill: Spill,
+    patch_header: Option<PatchHeader<BufWriter<W>>>,
+    byte_order: ByteOrder,
+}
+
+fn write_header<W: Write>(w: &mut W, pairs: u64, byte_order: ByteOrder) -> io::Result<()> {
+    let pairs = match byte_order {
+        ByteOrder::LittleEndian => pairs.to_le_bytes(),
+        ByteOrder::BigEndian => pairs.to_be_bytes(),
+    };
+    w.write_all(&pairs)?;
+    w.write_all(&pairs)
+}
+
+fn patch_header<W: Write + Seek>(
+    w: &mut W,
+    header_position: u64,
+    pairs: u64,
+    byte_order: ByteOrder,
+) -> io::Result<()> {
+    let end = w.stream_position()?;
+    w.seek(SeekFrom::Start(header_position))?;
+    write_header(w, pairs, byte_order)?;
+    w.seek(SeekFrom::Start(end))?;
+    Ok(())
+}
+
+impl<W: Write + Seek> MatchWriter<W> {
+    /// Write a placeholder header at the current position of `writer`,
+    /// the actual counts are written by `finish()`
+    pub fn new(writer: W) -> Result<Self, ASPMatchError> {
+        MatchWriter::with_byte_order(writer, ByteOrder::LittleEndian)
+    }
+
+    /// Same as [`MatchWriter::new`], writing records and header in `byte_order`
+    pub fn with_byte_order(writer: W, byte_order: ByteOrder) -> Result<Self, ASPMatchError> {
+        let mut writer = BufWriter::new(writer);
+        let header_position = writer.stream_position()?;
+        write_header(&mut writer, 0, byte_order)?;
+        Ok(MatchWriter {
+            writer,
+            header_position,
+            pairs: 0,
+            declared_pairs: None,
+            spill: Spill::TempFile(BufWriter::new(tempfile::tempfile()?)),
+            patch_header: Some(patch_header::<BufWriter<W>>),
+            byte_order,
+        })
+    }
+}
+
+impl<W: Write> MatchWriter<W> {
+    /// Write header for `pairs` pairs to a non seekable `writer`
+    ///
+    /// Exactly `pairs` pairs must then be written before calling `finish()`.
+    pub fn with_pair_count(writer: W, pairs: u64) -> Result<Self, ASPMatchError> {
+        MatchWriter::with_pair_count_and_byte_order(writer, pairs, ByteOrder::LittleEndian)
+    }
+
+    /// Same as [`MatchWriter::with_pair_count`], writing records and header in `byte_order`
+    pub fn with_pair_count_and_byte_order(
+        writer: W,
+        pairs: u64,
+        byte_order: ByteOrder,
+    ) -> Result<Self, ASPMatchError> {
+        let mut writer = BufWriter::new(writer);
+        write_header(&mut writer, pairs, byte_order)?;
+        Ok(MatchWriter {
+            writer,
+            header_position: 0,
+            pairs: 0,
+            declared_pairs: Some(pairs),
+            spill: Spill::Memory(vec![]),
+            patch_header: None,
+            byte_order,
+        })
+    }
+
+    /// Number of pairs written so far
+    pub fn pairs_written(&self) -> u64 {
+        self.pairs
+    }
+
+    /// Write one pair of matching records
+    pub fn write_pair(
+        &mut self,
+        image_1: &IPRecord,
+        image_2: &IPRecord,
+    ) -> Result<(), ASPMatchError> {
+        if let Some(declared) = self.declared_pairs {
+            if self.pairs >= declared {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("header declares {} pairs, cannot write more", declared),
+                )
+                .into());
+            }
+        }
+        image_1.write_bytes(&mut self.writer, self.byte_order)?;
+        match &mut self.spill {
+            Spill::TempFile(spill) => image_2.write_bytes(spill, self.byte_order)?,
+            Spill::Memory(spill) => image_2.write_bytes(spill, self.byte_order)?,
+        }
+        self.pairs += 1;
+        Ok(())
+    }
+
+    /// Append image 2 records, fix the header and return the underlying writer
+    pub fn finish(mut self) -> Result<W, ASPMatchError> {
+        if let Some(declared) = self.declared_pairs {
+            if self.pairs != declared {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "header declares {} pairs but {} were written",
+                        declared, self.pairs
+                    ),
+                )
+                .into());
+            }
+        }
+        match self.spill {
+            Spill::TempFile(spill) => {
+                let mut spill = spill.into_inner().map_err(|e| e.into_error())?;
+                spill.seek(SeekFrom::Start(0))?;
+                io::copy(&mut spill, &mut self.writer)?;
+            }
+            Spill::Memory(spill) => self.writer.write_all(&spill)?,
+        }
+        if let Some(patch_header) = self.patch_header {
+            patch_header(
+                &mut self.writer,
+                self.header_position,
+                self.pairs,
+                self.byte_order,
+            )?;
+        }
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::MatchSide;
+    use crate::data::{IPMatch, IPRecord};
+    use crate::options::{ByteOrder, ParseOptions};
+    use crate::parser::ipmatch;
+    use crate::reader::MatchReader;
+    use crate::writer::MatchWriter;
+    use std::io::Cursor;
+
+    fn dummy_pairs() -> Vec<(IPRecord, IPRecord)> {
+        (0..4)
+            .map(|i| {
+                let record = |x: f32| IPRecord {
+                    x,
+                    desc: vec![x; i],
+                    ..IPRecord::default()
+                };
+                (record(i as f32), record(10.0 + i as f32))
+            })
+            .collect()
+    }
+
+    fn dummy_ipmatch() -> IPMatch {
+        let (image_1, image_2) = dummy_pairs().into_iter().unzip();
+        IPMatch { image_1, image_2 }
+    }
+
+    #[test]
+    fn test_match_writer_seekable() {
+        let mut writer = MatchWriter::new(Cursor::new(vec![])).unwrap();
+        for (record_1, record_2) in dummy_pairs() {
+            writer.write_pair(&record_1, &record_2).unwrap();
+        }
+        assert_eq!(writer.pairs_written(), 4);
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(bytes, dummy_ipmatch().as_le_bytes());
+        let (_, parsed) = ipmatch(&bytes).unwrap();
+        assert_eq!(parsed, dummy_ipmatch());
+    }
+
+    #[test]
+    fn test_match_writer_with_pair_count() {
+        let mut writer = MatchWriter::with_pair_count(vec![], 4).unwrap();
+        for (record_1, record_2) in dummy_pairs() {
+            writer.write_pair(&record_1, &record_2).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes, dummy_ipmatch().as_le_bytes());
+    }
+
+    #[test]
+    fn test_match_writer_big_endian() {
+        let expected = dummy_ipmatch();
+        let options = ParseOptions {
+            byte_order: ByteOrder::BigEndian,
+            ..ParseOptions::default()
+        };
+        let mut writer =
+            MatchWriter::with_byte_order(Cursor::new(vec![]), ByteOrder::BigEndian).unwrap();
+        for (record_1, record_2) in dummy_pairs() {
+            writer.write_pair(&record_1, &record_2).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(bytes, expected.as_bytes(ByteOrder::BigEndian));
+        let mut parsed = IPMatch::default();
+        for item in MatchReader::with_options(bytes.as_slice(), &options).unwrap() {
+            match item.unwrap() {
+                (MatchSide::Image1, record) => parsed.image_1.push(record),
+                (MatchSide::Image2, record) => parsed.image_2.push(record),
+            }
+        }
+        assert_eq!(parsed, expected);
+
+        let mut writer =
+            MatchWriter::with_pair_count_and_byte_order(vec![], 4, ByteOrder::BigEndian).unwrap();
+        for (record_1, record_2) in dummy_pairs() {
+            writer.write_pair(&record_1, &record_2).unwrap();
+        }
+        assert_eq!(
+            writer.finish().unwrap(),
+            expected.as_bytes(ByteOrder::BigEndian)
+        );
+    }
+
+    #[test]
+    fn test_match_writer_with_pair_count_mismatch() {
+        let record = IPRecord::default();
+        let mut writer = MatchWriter::with_pair_count(vec![], 1).unwrap();
+        writer.write_pair(&record, &record).unwrap();
+        assert!(writer.write_pair(&record, &record).is_err());
+
+        let mut writer = MatchWriter::with_pair_count(vec![], 2).unwrap();
+        writer.write_pair(&record, &record).unwrap();
+        assert!(writer.finish().is_err());
+    }
+}